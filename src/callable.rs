@@ -0,0 +1,15 @@
+use core::fmt;
+use std::rc::Rc;
+
+use crate::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+
+/// Anything that can appear on the left of a call expression.
+///
+/// `call` takes the callable by `Rc` so that classes can hand a reference to
+/// themselves to the instances they create.
+pub trait LoxCallable: fmt::Debug + fmt::Display {
+    fn arity(&self) -> usize;
+    fn call(self: Rc<Self>, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError>;
+}
@@ -0,0 +1,198 @@
+use std::io::{self, Read};
+
+use rlox::emit::ErrorFormat;
+
+pub const USAGE: &str = "\
+Usage: rlox [options] [script | -]
+       rlox <command> [options] [script | - | -e <code>]
+
+Commands:
+  run      Run a script (the default)
+  repl     Start an interactive session (the default without a script)
+  tokens   Print the tokens of a script; --json prints one JSON object per token
+  ast      Print the syntax tree; --rpn prints expressions in reverse Polish notation
+  check    Scan, parse and resolve a script without running it
+  fmt      Print a script in the canonical layout
+
+Options:
+  -e <code>                          Use <code> as the script
+  --error-format=human|json|sarif    How diagnostics are written to stderr";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Repl,
+    Tokens,
+    Ast,
+    Check,
+    Fmt,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "run" => Some(Command::Run),
+            "repl" => Some(Command::Repl),
+            "tokens" => Some(Command::Tokens),
+            "ast" => Some(Command::Ast),
+            "check" => Some(Command::Check),
+            "fmt" => Some(Command::Fmt),
+            _ => None,
+        }
+    }
+}
+
+/// Where the script comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    File(String),
+    Stdin,
+    Eval(String),
+}
+
+impl Input {
+    /// The name diagnostics use for the script.
+    pub fn name(&self) -> &str {
+        match self {
+            Input::File(path) => path,
+            Input::Stdin => "<stdin>",
+            Input::Eval(_) => "<eval>",
+        }
+    }
+
+    pub fn read(&self) -> io::Result<String> {
+        match self {
+            Input::File(path) => std::fs::read_to_string(path),
+            Input::Stdin => {
+                let mut source = String::new();
+                io::stdin().read_to_string(&mut source)?;
+                Ok(source)
+            }
+            Input::Eval(code) => Ok(code.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub command: Command,
+    /// `None` only for the REPL.
+    pub input: Option<Input>,
+    pub error_format: ErrorFormat,
+    /// `tokens --json`
+    pub json: bool,
+    /// `ast --rpn`
+    pub rpn: bool,
+}
+
+/// Parses the command line, without the program name. Errors are messages to
+/// show above the usage text.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut command = None;
+    let mut input = None;
+    let mut error_format = ErrorFormat::default();
+    let mut json = false;
+    let mut rpn = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let next_input = match arg.as_str() {
+            "--json" => { json = true; None }
+            "--rpn" => { rpn = true; None }
+            "-e" => match args.next() {
+                Some(code) => Some(Input::Eval(code)),
+                None => return Err(String::from("Expected code after '-e'.")),
+            },
+            "-" => Some(Input::Stdin),
+            _ => {
+                if let Some(format) = arg.strip_prefix("--error-format=") {
+                    error_format = format.parse()?;
+                    None
+                } else if arg.starts_with('-') {
+                    return Err(format!("Unknown option '{}'.", arg));
+                } else if command.is_none() && input.is_none() && Command::from_name(&arg).is_some() {
+                    command = Command::from_name(&arg);
+                    None
+                } else {
+                    Some(Input::File(arg))
+                }
+            }
+        };
+
+        if let Some(next_input) = next_input {
+            if input.is_some() {
+                return Err(String::from("Expected at most one script."));
+            }
+            input = Some(next_input);
+        }
+    }
+
+    let command = match command {
+        Some(command) => command,
+        None if input.is_some() => Command::Run,
+        None => Command::Repl,
+    };
+
+    match command {
+        Command::Repl if input.is_some() => return Err(String::from("The REPL doesn't take a script.")),
+        Command::Repl => {}
+        _ if input.is_none() => return Err(String::from("Expected a script, '-' or '-e <code>'.")),
+        _ => {}
+    }
+
+    if json && command != Command::Tokens {
+        return Err(String::from("'--json' only applies to 'tokens'."));
+    }
+
+    if rpn && command != Command::Ast {
+        return Err(String::from("'--rpn' only applies to 'ast'."));
+    }
+
+    Ok(Options { command, input, error_format, json, rpn })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults_to_the_repl_or_running_a_script() {
+        let repl = parse(&[]).unwrap();
+        assert_eq!((repl.command, repl.input), (Command::Repl, None));
+
+        let run = parse(&["script.lox"]).unwrap();
+        assert_eq!((run.command, run.input), (Command::Run, Some(Input::File(String::from("script.lox")))));
+    }
+
+    #[test]
+    fn parses_commands_and_their_flags() {
+        let tokens = parse(&["tokens", "--json", "-"]).unwrap();
+        assert_eq!((tokens.command, tokens.input, tokens.json), (Command::Tokens, Some(Input::Stdin), true));
+
+        let ast = parse(&["ast", "-e", "print 1;", "--rpn"]).unwrap();
+        assert_eq!((ast.command, ast.input, ast.rpn), (Command::Ast, Some(Input::Eval(String::from("print 1;"))), true));
+
+        let check = parse(&["check", "--error-format=sarif", "a.lox"]).unwrap();
+        assert_eq!((check.command, check.error_format), (Command::Check, ErrorFormat::Sarif));
+
+        // A script may share its name with a command once the command is known.
+        let run = parse(&["run", "fmt"]).unwrap();
+        assert_eq!((run.command, run.input), (Command::Run, Some(Input::File(String::from("fmt")))));
+    }
+
+    #[test]
+    fn rejects_bad_command_lines() {
+        assert!(parse(&["a.lox", "b.lox"]).is_err());
+        assert!(parse(&["-e"]).is_err());
+        assert!(parse(&["--verbose", "a.lox"]).is_err());
+        assert!(parse(&["repl", "a.lox"]).is_err());
+        assert!(parse(&["check"]).is_err());
+        assert!(parse(&["run", "--json", "a.lox"]).is_err());
+        assert!(parse(&["tokens", "--rpn", "a.lox"]).is_err());
+        assert!(parse(&["--error-format=xml", "a.lox"]).is_err());
+    }
+}
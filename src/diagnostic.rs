@@ -0,0 +1,213 @@
+use core::fmt;
+use std::io::{self, Write};
+
+use crate::Token;
+use crate::emit::Emitter;
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Note => write!(f, "Note"),
+        }
+    }
+}
+
+/// The piece of source a diagnostic points at. An empty `text` means the end of the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub span: Span,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+impl From<&Token> for Location {
+    fn from(token: &Token) -> Self {
+        Location { span: token.span, line: token.line, column: token.column, text: token.lexeme.clone() }
+    }
+}
+
+/// A secondary location that helps explain a diagnostic, such as an earlier declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(location: Location, message: &str) -> Self {
+        Label { location, message: message.to_string() }
+    }
+}
+
+/// Error codes, one per phase of the pipeline.
+pub mod codes {
+    pub const SCAN_ERROR: &str = "E0001";
+    pub const PARSE_ERROR: &str = "E0100";
+    pub const RESOLVE_ERROR: &str = "E0200";
+    pub const RUNTIME_ERROR: &str = "E0300";
+
+    /// A short, human-readable name for `code`.
+    pub fn title(code: &str) -> &'static str {
+        match code {
+            SCAN_ERROR => "Scan error",
+            PARSE_ERROR => "Parse error",
+            RESOLVE_ERROR => "Resolve error",
+            RUNTIME_ERROR => "Runtime error",
+            _ => "Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<Location>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: &str) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.to_string(),
+            location: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some(ref location) if location.text.is_empty() => {
+                write!(f, "[line {}] {}[{}] at end: {}", location.line, self.severity, self.code, self.message)?
+            }
+            Some(ref location) => {
+                write!(f, "[line {}] {}[{}] at '{}': {}", location.line, self.severity, self.code, location.text, self.message)?
+            }
+            None => write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?,
+        }
+
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects the diagnostics produced by every phase of a run so they can be
+/// counted, filtered and written out together.
+#[derive(Debug, Default)]
+pub struct DiagnosticSink {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticSink {
+    pub fn new() -> Self {
+        DiagnosticSink { diagnostics: Vec::new() }
+    }
+
+    pub fn emit(&mut self, diagnostic: impl Into<Diagnostic>) {
+        self.diagnostics.push(diagnostic.into());
+    }
+
+    pub fn extend<D: Into<Diagnostic>>(&mut self, diagnostics: impl IntoIterator<Item = D>) {
+        self.diagnostics.extend(diagnostics.into_iter().map(Into::into));
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == severity).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    pub fn clear(&mut self) {
+        self.diagnostics.clear();
+    }
+
+    pub fn write_to(&self, out: &mut impl Write, emitter: &Emitter) -> io::Result<()> {
+        emitter.emit(out, &self.diagnostics)
+    }
+
+    /// Writes every collected diagnostic to stderr and empties the sink.
+    pub fn flush(&mut self, emitter: &Emitter) -> io::Result<()> {
+        self.write_to(&mut io::stderr().lock(), emitter)?;
+        self.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emit::ErrorFormat;
+
+    fn location(line: usize, text: &str) -> Location {
+        Location { span: Span::default(), line, column: 1, text: text.to_string() }
+    }
+
+    #[test]
+    fn formats_the_location_of_a_diagnostic() {
+        let at_token = Diagnostic::error(codes::PARSE_ERROR, "Expect expression.").with_location(location(3, ";"));
+        let at_end = Diagnostic::error(codes::PARSE_ERROR, "Expect ';' after value.").with_location(location(4, ""));
+        let nowhere = Diagnostic::error(codes::SCAN_ERROR, "Oops.");
+
+        assert_eq!(at_token.to_string(), "[line 3] Error[E0100] at ';': Expect expression.");
+        assert_eq!(at_end.to_string(), "[line 4] Error[E0100] at end: Expect ';' after value.");
+        assert_eq!(nowhere.to_string(), "Error[E0001]: Oops.");
+    }
+
+    #[test]
+    fn prints_notes_after_the_message() {
+        let mut diagnostic = Diagnostic::error(codes::RESOLVE_ERROR, "Bad.");
+        diagnostic.notes.push(String::from("try again"));
+        assert_eq!(diagnostic.to_string(), "Error[E0200]: Bad.\n  note: try again");
+    }
+
+    #[test]
+    fn sink_counts_and_writes_diagnostics() {
+        let mut sink = DiagnosticSink::new();
+        assert!(!sink.has_errors());
+
+        sink.emit(Diagnostic::error(codes::SCAN_ERROR, "one"));
+        sink.extend(vec![Diagnostic::error(codes::PARSE_ERROR, "two")]);
+        assert!(sink.has_errors());
+        assert_eq!(sink.count(Severity::Error), 2);
+        assert_eq!(sink.count(Severity::Warning), 0);
+
+        let mut out = Vec::new();
+        sink.write_to(&mut out, &Emitter::new(ErrorFormat::Human, "test.lox", "")).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "error[E0001]: one\n\nerror[E0100]: two\n\n");
+
+        sink.clear();
+        assert!(sink.diagnostics().is_empty());
+    }
+}
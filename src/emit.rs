@@ -0,0 +1,342 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::diagnostic::{codes, Diagnostic, Location, Severity};
+use crate::json::Json;
+use crate::render::Renderer;
+use crate::span::LineIndex;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// How diagnostics are written out, picked with `--error-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Source snippets with carets, for people.
+    #[default]
+    Human,
+    /// One JSON object per diagnostic per line, for editors.
+    Json,
+    /// A SARIF 2.1.0 log, for CI.
+    Sarif,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            other => Err(format!("Unknown error format '{}', expected human, json or sarif.", other)),
+        }
+    }
+}
+
+/// Writes a batch of diagnostics about one source file in the chosen format.
+pub struct Emitter<'a> {
+    format: ErrorFormat,
+    file_name: &'a str,
+    source: &'a str,
+    color: bool,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(format: ErrorFormat, file_name: &'a str, source: &'a str) -> Self {
+        Emitter { format, file_name, source, color: false }
+    }
+
+    /// Colors only ever apply to the human format.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn emit(&self, out: &mut impl Write, diagnostics: &[Diagnostic]) -> io::Result<()> {
+        match self.format {
+            ErrorFormat::Human => {
+                let renderer = Renderer::new(self.file_name, self.source).with_color(self.color);
+                for diagnostic in diagnostics {
+                    writeln!(out, "{}", renderer.render(diagnostic))?;
+                }
+            }
+            ErrorFormat::Json => {
+                let line_index = LineIndex::new(self.source);
+                for diagnostic in diagnostics {
+                    writeln!(out, "{}", self.json(&line_index, diagnostic))?;
+                }
+            }
+            // CI reads the log even after a clean run, so it is always written.
+            ErrorFormat::Sarif => writeln!(out, "{}", self.sarif(diagnostics).pretty())?,
+        }
+
+        Ok(())
+    }
+
+    fn json(&self, line_index: &LineIndex, diagnostic: &Diagnostic) -> Json {
+        let labels = diagnostic.labels.iter()
+            .map(|label| Json::object([
+                ("message", Json::str(&label.message)),
+                ("span", json_span(line_index, &label.location)),
+            ]))
+            .collect();
+
+        Json::object([
+            ("code", Json::str(diagnostic.code)),
+            ("severity", Json::str(severity_name(diagnostic.severity))),
+            ("message", Json::str(&diagnostic.message)),
+            ("file", Json::str(self.file_name)),
+            ("span", diagnostic.location.as_ref().map_or(Json::Null, |location| json_span(line_index, location))),
+            ("labels", Json::Array(labels)),
+            ("notes", Json::Array(diagnostic.notes.iter().map(|note| Json::str(note)).collect())),
+        ])
+    }
+
+    fn sarif(&self, diagnostics: &[Diagnostic]) -> Json {
+        let line_index = LineIndex::new(self.source);
+        let uri = artifact_uri(self.file_name);
+
+        let mut rule_ids: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.code).collect();
+        rule_ids.sort_unstable();
+        rule_ids.dedup();
+
+        let rules = rule_ids.iter()
+            .map(|&id| Json::object([
+                ("id", Json::str(id)),
+                ("shortDescription", Json::object([("text", Json::str(codes::title(id)))])),
+            ]))
+            .collect();
+
+        let results = diagnostics.iter()
+            .map(|diagnostic| {
+                let mut result = vec![
+                    ("ruleId", Json::str(diagnostic.code)),
+                    ("level", Json::str(severity_name(diagnostic.severity))),
+                    ("message", Json::object([("text", Json::str(&diagnostic.message))])),
+                ];
+
+                if let (Some(location), Some(uri)) = (&diagnostic.location, &uri) {
+                    result.push(("locations", Json::Array(vec![sarif_location(&line_index, uri, location, None)])));
+                }
+
+                if !diagnostic.labels.is_empty() {
+                    let related = diagnostic.labels.iter().enumerate()
+                        .map(|(id, label)| {
+                            let physical = uri.as_ref().map(|uri| (uri.as_str(), &label.location));
+                            sarif_related_location(&line_index, physical, id, &label.message)
+                        })
+                        .collect();
+                    result.push(("relatedLocations", Json::Array(related)));
+                }
+
+                if !diagnostic.notes.is_empty() {
+                    let notes = diagnostic.notes.iter().map(|note| Json::str(note)).collect();
+                    result.push(("properties", Json::object([("notes", Json::Array(notes))])));
+                }
+
+                Json::object(result)
+            })
+            .collect();
+
+        Json::object([
+            ("$schema", Json::str(SARIF_SCHEMA)),
+            ("version", Json::str("2.1.0")),
+            ("runs", Json::Array(vec![Json::object([
+                ("tool", Json::object([("driver", Json::object([
+                    ("name", Json::str(env!("CARGO_PKG_NAME"))),
+                    ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+                    ("rules", Json::Array(rules)),
+                ]))])),
+                // Our columns count characters, not the UTF-16 code units SARIF assumes by default.
+                ("columnKind", Json::str("unicodeCodePoints")),
+                ("results", Json::Array(results)),
+            ])])),
+        ])
+    }
+}
+
+/// Where `uri` points, as a SARIF location with a physical location.
+fn sarif_location(line_index: &LineIndex, uri: &str, location: &Location, label: Option<(usize, &str)>) -> Json {
+    let mut members = Vec::new();
+    if let Some((id, _)) = label {
+        members.push(("id", Json::from(id)));
+    }
+    members.push(("physicalLocation", sarif_physical_location(line_index, uri, location)));
+    if let Some((_, message)) = label {
+        members.push(("message", Json::object([("text", Json::str(message))])));
+    }
+
+    Json::object(members)
+}
+
+/// A label as a related location, which only has a message when the file has no URI.
+fn sarif_related_location(line_index: &LineIndex, physical: Option<(&str, &Location)>, id: usize, message: &str) -> Json {
+    match physical {
+        Some((uri, location)) => sarif_location(line_index, uri, location, Some((id, message))),
+        None => Json::object([
+            ("id", Json::from(id)),
+            ("message", Json::object([("text", Json::str(message))])),
+        ]),
+    }
+}
+
+fn sarif_physical_location(line_index: &LineIndex, uri: &str, location: &Location) -> Json {
+    let (end_line, end_column) = line_index.line_column(location.span.end_byte);
+    let region = Json::object([
+        ("startLine", Json::from(location.line)),
+        ("startColumn", Json::from(location.column)),
+        ("endLine", Json::from(end_line)),
+        ("endColumn", Json::from(end_column)),
+        ("byteOffset", Json::from(location.span.start_byte)),
+        ("byteLength", Json::from(location.span.len())),
+    ]);
+
+    Json::object([
+        ("artifactLocation", Json::object([("uri", Json::str(uri))])),
+        ("region", region),
+    ])
+}
+
+/// The URI SARIF wants for `file_name`: a relative reference for a relative
+/// path and a `file://` URI for an absolute one. Pseudo-inputs such as
+/// `<stdin>` have no URI at all.
+fn artifact_uri(file_name: &str) -> Option<String> {
+    if file_name.starts_with('<') {
+        return None;
+    }
+
+    let absolute = Path::new(file_name).is_absolute();
+    let path = file_name.replace(std::path::MAIN_SEPARATOR, "/");
+    let mut uri = match (absolute, path.starts_with('/')) {
+        (false, _) => String::new(),
+        (true, true) => String::from("file://"),
+        // A Windows path such as `C:/dir` still needs the empty authority.
+        (true, false) => String::from("file:///"),
+    };
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            // A colon would read as a scheme at the start of a relative reference.
+            b':' if absolute => uri.push(':'),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    Some(uri)
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+/// A span as 1-based lines and columns, end exclusive, plus the raw byte offsets.
+fn json_span(line_index: &LineIndex, location: &Location) -> Json {
+    let (end_line, end_column) = line_index.line_column(location.span.end_byte);
+
+    Json::object([
+        ("start_byte", Json::from(location.span.start_byte)),
+        ("end_byte", Json::from(location.span.end_byte)),
+        ("start_line", Json::from(location.line)),
+        ("start_column", Json::from(location.column)),
+        ("end_line", Json::from(end_line)),
+        ("end_column", Json::from(end_column)),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostic::DiagnosticSink;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    const SOURCE: &str = "{\n  var a = \"é\";\n  var a = 2;\n}\n";
+
+    /// The diagnostics for `source`, from whichever phase fails first.
+    fn diagnostics(source: &str) -> Vec<Diagnostic> {
+        let mut sink = DiagnosticSink::new();
+        let mut scanner = Scanner::new(source);
+        match scanner.scan_tokens() {
+            Err(errors) => sink.extend(errors),
+            Ok(tokens) => match Parser::new(tokens).parse() {
+                Err(errors) => sink.extend(errors),
+                Ok(statements) => {
+                    if let Err(errors) = Resolver::new().resolve(&statements) {
+                        sink.extend(errors);
+                    }
+                }
+            },
+        }
+        sink.diagnostics().to_vec()
+    }
+
+    fn emit(format: ErrorFormat, source: &str) -> String {
+        let mut out = Vec::new();
+        Emitter::new(format, "test.lox", source).emit(&mut out, &diagnostics(source)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parses_error_formats() {
+        assert_eq!("human".parse(), Ok(ErrorFormat::Human));
+        assert_eq!("json".parse(), Ok(ErrorFormat::Json));
+        assert_eq!("sarif".parse(), Ok(ErrorFormat::Sarif));
+        assert!("xml".parse::<ErrorFormat>().is_err());
+    }
+
+    #[test]
+    fn human_snapshot() {
+        assert_eq!(emit(ErrorFormat::Human, SOURCE), include_str!("snapshots/redeclaration.human"));
+    }
+
+    #[test]
+    fn json_snapshot() {
+        assert_eq!(emit(ErrorFormat::Json, SOURCE), include_str!("snapshots/redeclaration.json"));
+        assert_eq!(emit(ErrorFormat::Json, "print 1 @ 2"), include_str!("snapshots/scan_errors.json"));
+    }
+
+    #[test]
+    fn sarif_snapshot() {
+        assert_eq!(emit(ErrorFormat::Sarif, SOURCE), include_str!("snapshots/redeclaration.sarif"));
+    }
+
+    #[test]
+    fn nothing_is_written_without_diagnostics() {
+        for format in [ErrorFormat::Human, ErrorFormat::Json] {
+            assert_eq!(emit(format, "print 1;"), "");
+        }
+    }
+
+    #[test]
+    fn a_clean_run_still_writes_a_sarif_log() {
+        let log = emit(ErrorFormat::Sarif, "print 1;");
+        assert!(log.starts_with("{\n  \"$schema\""));
+        assert!(log.contains("\"results\": []"));
+    }
+
+    #[test]
+    fn sarif_uris_are_relative_references_or_file_uris() {
+        assert_eq!(artifact_uri("test.lox").as_deref(), Some("test.lox"));
+        assert_eq!(artifact_uri("dir/my file.lox").as_deref(), Some("dir/my%20file.lox"));
+        assert_eq!(artifact_uri("<stdin>"), None);
+        if cfg!(unix) {
+            assert_eq!(artifact_uri("/tmp/a#b.lox").as_deref(), Some("file:///tmp/a%23b.lox"));
+        }
+    }
+
+    #[test]
+    fn pseudo_inputs_have_no_physical_locations() {
+        let mut out = Vec::new();
+        Emitter::new(ErrorFormat::Sarif, "<eval>", SOURCE).emit(&mut out, &diagnostics(SOURCE)).unwrap();
+        let log = String::from_utf8(out).unwrap();
+
+        assert!(!log.contains("physicalLocation"));
+        assert!(log.contains("\"ruleId\": \"E0200\""));
+        assert!(log.contains("previous declaration here"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::{Token, RuntimeError};
+use crate::value::Value;
+
+/// A single scope of variable bindings, linked to the scope that encloses it.
+///
+/// A `var` declaration without an initializer binds the variable to `nil`, so
+/// reading it before any assignment is not an error.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment { values: HashMap::new(), enclosing: None }
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment { values: HashMap::new(), enclosing: Some(enclosing) }
+    }
+
+    /// Binds `name` in this scope, shadowing any outer binding and silently
+    /// replacing a previous binding in the same scope.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match self.enclosing {
+            Some(ref enclosing) => enclosing.borrow().get(name),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    /// Reads `name` from the scope exactly `distance` hops up the chain, as computed by the resolver.
+    pub fn get_at(&self, distance: usize, name: &str) -> Option<Value> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().get_at(distance - 1, name))
+    }
+
+    /// Assigns to the nearest scope that binds `name`. Assignment never creates a new binding.
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match self.enclosing {
+            Some(ref enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value);
+            return Ok(());
+        }
+
+        match self.enclosing {
+            Some(ref enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(Environment::undefined(name)),
+        }
+    }
+
+    fn undefined(name: &Token) -> RuntimeError {
+        RuntimeError::new(name.clone(), &format!("Undefined variable '{}'.", name.lexeme))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TokenType;
+
+    fn identifier(name: &str) -> Token {
+        Token::new(TokenType::IDENTIFIER, name, None, 1)
+    }
+
+    #[test]
+    fn looks_up_enclosing_scopes() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+
+        let local = Environment::with_enclosing(globals);
+        assert_eq!(local.get(&identifier("a")).unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn inner_definitions_shadow_outer_ones() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+
+        let mut local = Environment::with_enclosing(globals.clone());
+        local.define("a", Value::Number(2.0));
+
+        assert_eq!(local.get(&identifier("a")).unwrap(), Value::Number(2.0));
+        assert_eq!(globals.borrow().get(&identifier("a")).unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn assigns_to_the_scope_that_defines_the_variable() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+
+        let mut local = Environment::with_enclosing(globals.clone());
+        local.assign(&identifier("a"), Value::Number(3.0)).unwrap();
+
+        assert_eq!(globals.borrow().get(&identifier("a")).unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn reads_and_writes_at_a_fixed_distance() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Value::Number(1.0));
+
+        let mut local = Environment::with_enclosing(globals.clone());
+        local.define("a", Value::Number(2.0));
+
+        assert_eq!(local.get_at(1, "a"), Some(Value::Number(1.0)));
+        local.assign_at(1, &identifier("a"), Value::Number(3.0)).unwrap();
+        assert_eq!(local.get_at(0, "a"), Some(Value::Number(2.0)));
+        assert_eq!(globals.borrow().get_at(0, "a"), Some(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn undefined_variables_are_runtime_errors() {
+        let mut env = Environment::new();
+        let name = Token::new(TokenType::IDENTIFIER, "x", None, 7);
+
+        assert_eq!(env.get(&name).unwrap_err().to_string(), "[line 7] Undefined variable 'x'.");
+        assert_eq!(
+            env.assign(&name, Value::Nil).unwrap_err().to_string(),
+            "[line 7] Undefined variable 'x'."
+        );
+    }
+}
@@ -1,7 +1,157 @@
-pub fn error(line: usize, message: String) {
-    report(line, "".to_string(), message)
+use core::fmt;
+
+use super::{Token, TokenType};
+use crate::diagnostic::{codes, Diagnostic, Label, Location};
+
+fn location(token: &Token) -> String {
+    match token.kind {
+        TokenType::EOF => "at end".to_string(),
+        _ => format!("at '{}'", token.lexeme),
+    }
+}
+
+/// A lexical error. The scanner has no token to point at yet, only the offending text.
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub location: Location,
+    pub message: String,
+    pub labels: Vec<Label>,
+}
+
+impl ScanError {
+    pub fn new(location: Location, message: &str) -> Self {
+        ScanError { location, message: message.to_string(), labels: Vec::new() }
+    }
+
+    pub fn with_label(mut self, location: Location, message: &str) -> Self {
+        self.labels.push(Label::new(location, message));
+        self
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error: {}", self.location.line, self.message)
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl From<ScanError> for Diagnostic {
+    fn from(error: ScanError) -> Self {
+        let mut diagnostic = Diagnostic::error(codes::SCAN_ERROR, &error.message).with_location(error.location);
+        diagnostic.labels = error.labels;
+        diagnostic
+    }
+}
+
+/// An error raised while the program runs. Native functions raise theirs
+/// without a token; the call expression fills in its own.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub token: Option<Token>,
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(token: Token, message: &str) -> Self {
+        RuntimeError { token: Some(token), message: message.to_string() }
+    }
+
+    /// An error that doesn't know where it happened yet.
+    pub fn message(message: &str) -> Self {
+        RuntimeError { token: None, message: message.to_string() }
+    }
+
+    /// Points the error at `token` unless it already has a location.
+    pub fn or_at(mut self, token: &Token) -> Self {
+        if self.token.is_none() {
+            self.token = Some(token.clone());
+        }
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.token {
+            Some(ref token) => write!(f, "[line {}] {}", token.line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
 }
 
-fn report(line: usize, r#where: String, message: String) {
-    println!("[line {}] Error {}: {}", line, r#where, message)
+impl std::error::Error for RuntimeError {}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        let diagnostic = Diagnostic::error(codes::RUNTIME_ERROR, &error.message);
+        match error.token {
+            Some(ref token) => diagnostic.with_location(Location::from(token)),
+            None => diagnostic,
+        }
+    }
+}
+
+/// A syntax error, reported at the token where the parser got stuck.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(token: Token, message: &str) -> Self {
+        ParseError { token, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error {}: {}", self.token.line, location(&self.token), self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        Diagnostic::error(codes::PARSE_ERROR, &error.message).with_location(Location::from(&error.token))
+    }
+}
+
+/// A static error found by the resolver before the program runs.
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub token: Token,
+    pub message: String,
+    pub labels: Vec<Label>,
+}
+
+impl ResolveError {
+    pub fn new(token: Token, message: &str) -> Self {
+        ResolveError { token, message: message.to_string(), labels: Vec::new() }
+    }
+
+    pub fn with_label(mut self, location: Location, message: &str) -> Self {
+        self.labels.push(Label::new(location, message));
+        self
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error {}: {}", self.token.line, location(&self.token), self.message)
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+impl From<ResolveError> for Diagnostic {
+    fn from(error: ResolveError) -> Self {
+        let mut diagnostic = Diagnostic::error(codes::RESOLVE_ERROR, &error.message)
+            .with_location(Location::from(&error.token));
+        diagnostic.labels = error.labels;
+        diagnostic
+    }
 }
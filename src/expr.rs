@@ -1,14 +1,31 @@
-use super::Token;
+use std::cell::Cell;
+
+use super::{Token, LiteralValue};
+
+/// How many scopes separate a variable use from its declaration, filled in by the
+/// resolver. `None` means the variable is looked up in the globals.
+pub type Depth = Cell<Option<usize>>;
 
 #[derive(Debug, Clone)]
-pub enum Expr<'a> {
-    Binary(Box<Expr<'a>>, Token<'a>, Box<Expr<'a>>),
-    Grouping(Box<Expr<'a>>),
-    Literal(String),
-    Unary(Token<'a>, Box<Expr<'a>>),
+pub enum Expr {
+    Binary(Box<Expr>, Token, Box<Expr>),
+    Grouping(Box<Expr>),
+    Literal(LiteralValue),
+    Unary(Token, Box<Expr>),
+    Variable(Token, Depth),
+    Assign(Token, Box<Expr>, Depth),
+    Logical(Box<Expr>, Token, Box<Expr>),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    This(Token, Depth),
+    Super(Token, Token, Depth),
+    /// The literal fragments and embedded expressions of an interpolated
+    /// string, in source order. Empty fragments are left out.
+    Interpolation(Vec<Expr>),
 }
 
-impl<'a> std::fmt::Display for Expr<'a> {
+impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
             Expr::Binary(ref left, ref operator, ref right) => {
@@ -17,17 +34,42 @@ impl<'a> std::fmt::Display for Expr<'a> {
             Expr::Grouping(ref expr) => write!(f, "(group {})", expr),
             Expr::Literal(ref value) => write!(f, "{}", value),
             Expr::Unary(ref operator, ref expr) => write!(f, "({} {})", operator.lexeme, expr),
+            Expr::Variable(ref name, _) => write!(f, "{}", name.lexeme),
+            Expr::Assign(ref name, ref value, _) => write!(f, "(= {} {})", name.lexeme, value),
+            Expr::Logical(ref left, ref operator, ref right) => {
+                write!(f, "({} {} {})", operator.lexeme, left, right)
+            }
+            Expr::Call(ref callee, _, ref arguments) => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Get(ref object, ref name) => write!(f, "(. {} {})", object, name.lexeme),
+            Expr::Set(ref object, ref name, ref value) => {
+                write!(f, "(= (. {} {}) {})", object, name.lexeme, value)
+            }
+            Expr::This(..) => write!(f, "this"),
+            Expr::Super(_, ref method, _) => write!(f, "(super {})", method.lexeme),
+            Expr::Interpolation(ref parts) => {
+                write!(f, "(interpolate")?;
+                for part in parts {
+                    write!(f, " {}", part)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
 
-pub trait ExprVisitor<'a, T> {
-    fn visit(&mut self, expr: &Expr<'a>) -> T;
+pub trait ExprVisitor<T> {
+    fn visit(&mut self, expr: &Expr) -> T;
 }
 
-struct ASTPrinter;
-impl<'a> ExprVisitor<'a, String> for ASTPrinter {
-    fn visit(&mut self, expr: &Expr<'a>) -> String {
+pub struct ASTPrinter;
+impl ExprVisitor<String> for ASTPrinter {
+    fn visit(&mut self, expr: &Expr) -> String {
         match expr {
             Expr::Binary(ref left, ref operator, ref right) => {
                 format!("({} {} {})", 
@@ -39,33 +81,86 @@ impl<'a> ExprVisitor<'a, String> for ASTPrinter {
             Expr::Grouping(ref expr) => {
                 format!("(group {})", self.visit(expr))
             }
-            Expr::Literal(ref value) => format!("{}", value),
+            Expr::Literal(ref value) => value.to_string(),
             Expr::Unary(ref operator, ref expr) => {
                 format!("({} {})", 
                     operator.lexeme, 
                     self.visit(expr)
                 )
             }
+            Expr::Variable(ref name, _) => name.lexeme.to_string(),
+            Expr::Assign(ref name, ref value, _) => {
+                format!("(= {} {})", name.lexeme, self.visit(value))
+            }
+            Expr::Logical(ref left, ref operator, ref right) => {
+                format!("({} {} {})", 
+                    operator.lexeme, 
+                    self.visit(left), 
+                    self.visit(right)
+                )
+            }
+            Expr::Call(ref callee, _, ref arguments) => {
+                let mut parts = vec![String::from("call"), self.visit(callee)];
+                parts.extend(arguments.iter().map(|argument| self.visit(argument)));
+                format!("({})", parts.join(" "))
+            }
+            Expr::Get(ref object, ref name) => {
+                format!("(. {} {})", self.visit(object), name.lexeme)
+            }
+            Expr::Set(ref object, ref name, ref value) => {
+                format!("(= (. {} {}) {})", self.visit(object), name.lexeme, self.visit(value))
+            }
+            Expr::This(..) => String::from("this"),
+            Expr::Super(_, ref method, _) => format!("(super {})", method.lexeme),
+            Expr::Interpolation(ref parts) => {
+                let mut strings = vec![String::from("interpolate")];
+                strings.extend(parts.iter().map(|part| self.visit(part)));
+                format!("({})", strings.join(" "))
+            }
         }
     }
 }
 
-struct RPNPrinter;
-impl <'a> ExprVisitor<'a, String> for RPNPrinter {
-    fn visit(&mut self, expr: &Expr<'a>) -> String {
+pub struct RPNPrinter;
+impl ExprVisitor<String> for RPNPrinter {
+    fn visit(&mut self, expr: &Expr) -> String {
         match expr {
             Expr::Binary(ref left, ref operator, ref right) => {
                 format!("{} {} {}", self.visit(left), self.visit(right), operator.lexeme)
             }
             Expr::Grouping(ref expr) => {
-                format!("{}", self.visit(expr))
-            }
-            Expr::Literal(ref value) => {
-                format!("{}", value)
+                self.visit(expr)
             }
+            Expr::Literal(ref value) => value.to_string(),
             Expr::Unary(ref operator, ref expr) => {
                 format!("{}({})", operator.lexeme, self.visit(expr))
             }
+            Expr::Variable(ref name, _) => name.lexeme.to_string(),
+            Expr::Assign(ref name, ref value, _) => {
+                format!("{} {} =", name.lexeme, self.visit(value))
+            }
+            Expr::Logical(ref left, ref operator, ref right) => {
+                format!("{} {} {}", self.visit(left), self.visit(right), operator.lexeme)
+            }
+            Expr::Call(ref callee, _, ref arguments) => {
+                let mut parts: Vec<String> = arguments.iter().map(|argument| self.visit(argument)).collect();
+                parts.push(self.visit(callee));
+                parts.push(String::from("call"));
+                parts.join(" ")
+            }
+            Expr::Get(ref object, ref name) => {
+                format!("{} {} .", self.visit(object), name.lexeme)
+            }
+            Expr::Set(ref object, ref name, ref value) => {
+                format!("{} {} . {} =", self.visit(object), name.lexeme, self.visit(value))
+            }
+            Expr::This(..) => String::from("this"),
+            Expr::Super(_, ref method, _) => format!("super {} .", method.lexeme),
+            Expr::Interpolation(ref parts) => {
+                let mut strings: Vec<String> = parts.iter().map(|part| self.visit(part)).collect();
+                strings.push(String::from("interpolate"));
+                strings.join(" ")
+            }
         }
     }
 }
@@ -76,7 +171,7 @@ mod test {
 
     #[test]
     fn can_visit_simple_expression() {
-        let simple_expression = Expr::Literal(String::from("Potato"));
+        let simple_expression = Expr::Literal(LiteralValue::Str(String::from("Potato")));
         let mut visitor = ASTPrinter{};
 
         assert_eq!(visitor.visit(&simple_expression), format!("{}", &simple_expression));
@@ -85,9 +180,9 @@ mod test {
     #[test]
     fn can_visit_composite_expression() {
         let composite_expression = Expr::Binary(
-            Box::new(Expr::Literal(String::from("Potato"))),
+            Box::new(Expr::Literal(LiteralValue::Str(String::from("Potato")))),
             Token::new(crate::TokenType::PLUS, "+", None, 0),
-            Box::new(Expr::Literal(String::from("Potato")))
+            Box::new(Expr::Literal(LiteralValue::Str(String::from("Potato"))))
         );
         let mut visitor = ASTPrinter{};
 
@@ -100,13 +195,13 @@ mod test {
         let complex_expression = Expr::Binary(
             Box::new(Expr::Unary(
                 Token::new(crate::TokenType::PLUS, "+", None, 0),
-                Box::new(Expr::Literal(String::from("variableA")))
+                Box::new(Expr::Literal(LiteralValue::Str(String::from("variableA"))))
             )),
             Token::new(crate::TokenType::PLUS, "+", None, 0),
             Box::new(Expr::Binary(
-                Box::new(Expr::Literal(String::from("variableB"))), 
+                Box::new(Expr::Literal(LiteralValue::Str(String::from("variableB")))), 
                 Token::new(crate::TokenType::STAR, "*", None, 0),
-                Box::new(Expr::Literal(String::from("variableC"))), 
+                Box::new(Expr::Literal(LiteralValue::Str(String::from("variableC")))), 
             ))
         );
         let mut visitor = ASTPrinter{};
@@ -120,11 +215,11 @@ mod test {
         let expression = Expr::Binary(
             Box::new(Expr::Unary(
                 Token::new(crate::TokenType::MINUS, "-", None, 0),
-                Box::new(Expr::Literal(String::from("123")))
+                Box::new(Expr::Literal(LiteralValue::Number(123.0)))
             )),
             Token::new(crate::TokenType::STAR, "*", None, 0),
             Box::new(Expr::Grouping(
-                Box::new(Expr::Literal(String::from("45.67")))
+                Box::new(Expr::Literal(LiteralValue::Number(45.67)))
             ))
         );
         let mut visitor = ASTPrinter{};
@@ -138,17 +233,17 @@ mod test {
         let expression = Box::new(Expr::Binary(
             Box::new(Expr::Grouping(
                 Box::new(Expr::Binary(
-                    Box::new(Expr::Literal(String::from("1"))), 
+                    Box::new(Expr::Literal(LiteralValue::Number(1.0))), 
                     Token::new(crate::TokenType::PLUS, "+", None, 0), 
-                    Box::new(Expr::Literal(String::from("2"))), 
+                    Box::new(Expr::Literal(LiteralValue::Number(2.0))), 
                 ))
             )),
             Token::new(crate::TokenType::STAR, "*", None, 0), 
             Box::new(Expr::Grouping(
                 Box::new(Expr::Binary(
-                    Box::new(Expr::Literal(String::from("4"))), 
+                    Box::new(Expr::Literal(LiteralValue::Number(4.0))), 
                     Token::new(crate::TokenType::MINUS, "-", None, 0), 
-                    Box::new(Expr::Literal(String::from("3"))), 
+                    Box::new(Expr::Literal(LiteralValue::Number(3.0))), 
                 ))
             )),
         ));
@@ -0,0 +1,291 @@
+use crate::{Token, TokenType, TriviaKind};
+
+const INDENT: &str = "    ";
+
+/// Lays out the tokens of a program that already parses in one canonical
+/// style: four-space indentation per block, one statement per line, single
+/// spaces around binary operators and at most one blank line kept between
+/// statements.
+struct Formatter {
+    out: String,
+    indent: usize,
+    /// Inside parentheses a `;` belongs to a `for` clause and doesn't end the line.
+    paren_depth: usize,
+    at_line_start: bool,
+    /// The last line written opened a block, so no blank line may follow it.
+    opened_block: bool,
+    /// A comment was just written mid-line and needs a space after it.
+    after_comment: bool,
+}
+
+/// Formats a program from its tokens. Tokens scanned
+/// [`with_trivia`](crate::Scanner::with_trivia) keep their comments: a
+/// comment on a line of its own stays on its own line, and one after code
+/// stays after it. Without trivia the comments are lost.
+pub fn format(tokens: &[Token]) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        indent: 0,
+        paren_depth: 0,
+        at_line_start: true,
+        opened_block: false,
+        after_comment: false,
+    };
+
+    for (i, token) in tokens.iter().enumerate() {
+        let blank_before = formatter.leading_comments(token);
+        if token.kind == TokenType::EOF {
+            break;
+        }
+
+        let previous = i.checked_sub(1).map(|i| &tokens[i]);
+        let before_previous = i.checked_sub(2).map(|i| &tokens[i]);
+        let next = tokens.get(i + 1).filter(|next| next.kind != TokenType::EOF);
+
+        if formatter.at_line_start {
+            if token.kind == TokenType::RIGHT_BRACE {
+                formatter.indent = formatter.indent.saturating_sub(1);
+            }
+            formatter.start_line(blank_before && token.kind != TokenType::RIGHT_BRACE);
+        } else if let Some(previous) = previous {
+            if formatter.after_comment || needs_space(before_previous, previous, token) {
+                formatter.out.push(' ');
+            }
+        }
+
+        formatter.out.push_str(token.source_text());
+        formatter.after_comment = false;
+        let line_comment = formatter.trailing_comments(token);
+
+        match token.kind {
+            TokenType::LEFT_PAREN => formatter.paren_depth += 1,
+            TokenType::RIGHT_PAREN => formatter.paren_depth = formatter.paren_depth.saturating_sub(1),
+            TokenType::LEFT_BRACE if !line_comment && next.is_some_and(|next| next.kind == TokenType::RIGHT_BRACE && !has_comments(next)) => {}
+            TokenType::LEFT_BRACE => {
+                formatter.indent += 1;
+                formatter.end_line();
+                formatter.opened_block = true;
+            }
+            TokenType::RIGHT_BRACE if !line_comment && next.is_some_and(|next| next.kind == TokenType::ELSE) => {}
+            TokenType::RIGHT_BRACE => formatter.end_line(),
+            TokenType::SEMICOLON if formatter.paren_depth == 0 => formatter.end_line(),
+            _ => {}
+        }
+
+        // A line comment runs to the end of the line, whatever comes next.
+        if line_comment && !formatter.at_line_start {
+            formatter.end_line();
+        }
+    }
+
+    if !formatter.out.is_empty() && !formatter.out.ends_with('\n') {
+        formatter.out.push('\n');
+    }
+
+    formatter.out
+}
+
+impl Formatter {
+    fn end_line(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    /// Indents a new line, after one blank line if the source had some
+    /// there, except at the start of a block.
+    fn start_line(&mut self, blank_before: bool) {
+        if blank_before && !self.opened_block && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out.push_str(&INDENT.repeat(self.indent));
+        self.at_line_start = false;
+        self.opened_block = false;
+        self.after_comment = false;
+    }
+
+    /// Writes the comments before `token` and returns whether a blank line
+    /// separates the last of them, or the code before, from the token.
+    fn leading_comments(&mut self, token: &Token) -> bool {
+        let trivia = token.leading_trivia();
+        let mut newlines = 0;
+        for (i, piece) in trivia.iter().enumerate() {
+            match piece.kind {
+                TriviaKind::Newline => newlines += 1,
+                TriviaKind::Whitespace => {}
+                TriviaKind::LineComment | TriviaKind::BlockComment => {
+                    if newlines > 0 || self.at_line_start {
+                        if !self.at_line_start {
+                            self.end_line();
+                        }
+                        self.start_line(newlines > 1);
+                    } else {
+                        self.out.push(' ');
+                    }
+                    self.out.push_str(piece.text.trim_end());
+
+                    let ends_line = piece.kind == TriviaKind::LineComment
+                        || trivia[i + 1..].iter().find(|next| next.kind != TriviaKind::Whitespace)
+                            .is_some_and(|next| next.kind == TriviaKind::Newline);
+                    if ends_line {
+                        self.end_line();
+                    } else {
+                        self.after_comment = true;
+                    }
+                    newlines = 0;
+                }
+            }
+        }
+
+        newlines > 1
+    }
+
+    /// Writes the comments after `token` on its line and returns whether
+    /// the last was a line comment, which the line has to end after.
+    fn trailing_comments(&mut self, token: &Token) -> bool {
+        for piece in token.trailing_trivia() {
+            match piece.kind {
+                TriviaKind::LineComment => {
+                    self.out.push(' ');
+                    self.out.push_str(piece.text.trim_end());
+                    return true;
+                }
+                TriviaKind::BlockComment => {
+                    self.out.push(' ');
+                    self.out.push_str(&piece.text);
+                    self.after_comment = true;
+                }
+                TriviaKind::Whitespace | TriviaKind::Newline => {}
+            }
+        }
+
+        false
+    }
+}
+
+fn has_comments(token: &Token) -> bool {
+    token.leading_trivia().iter().any(|piece| matches!(piece.kind, TriviaKind::LineComment | TriviaKind::BlockComment))
+}
+
+fn needs_space(before_previous: Option<&Token>, previous: &Token, current: &Token) -> bool {
+    use TokenType::*;
+
+    if previous.kind == INTERPOLATION || current.resumes_string() {
+        return false;
+    }
+
+    match (previous.kind, current.kind) {
+        (_, SEMICOLON | COMMA | RIGHT_PAREN | DOT) => false,
+        (LEFT_PAREN | DOT | BANG, _) => false,
+        (LEFT_BRACE, RIGHT_BRACE) => false,
+        (IDENTIFIER | RIGHT_PAREN | THIS, LEFT_PAREN) => false,
+        (MINUS, _) => is_operand(before_previous),
+        _ => true,
+    }
+}
+
+/// Whether `token` ends an operand, which makes a following `-` binary rather than unary.
+fn is_operand(token: Option<&Token>) -> bool {
+    use TokenType::*;
+
+    matches!(token.map(|token| token.kind), Some(NUMBER | STRING | IDENTIFIER | RIGHT_PAREN | TRUE | FALSE | NIL | THIS))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn format_source(source: &str) -> String {
+        let tokens = Scanner::new(source).with_trivia().scan_tokens().expect("scanning failed");
+        format(&tokens)
+    }
+
+    #[test]
+    fn lays_out_statements_and_blocks() {
+        let source = "var a=-1;fun f( x,y ){if(!x){return y*-a;}else{print f(x-1,y) ;}}\n\n\n\nclass B<A{init(){super.init();this.x=nil;}}";
+
+        assert_eq!(format_source(source), concat!(
+            "var a = -1;\n",
+            "fun f(x, y) {\n",
+            "    if (!x) {\n",
+            "        return y * -a;\n",
+            "    } else {\n",
+            "        print f(x - 1, y);\n",
+            "    }\n",
+            "}\n",
+            "\n",
+            "class B < A {\n",
+            "    init() {\n",
+            "        super.init();\n",
+            "        this.x = nil;\n",
+            "    }\n",
+            "}\n",
+        ));
+    }
+
+    #[test]
+    fn keeps_for_clauses_and_empty_blocks_on_one_line() {
+        assert_eq!(
+            format_source("for(var i=0;i<3;i=i+1){}\nfor(;;)print 1;"),
+            "for (var i = 0; i < 3; i = i + 1) {}\nfor (;;) print 1;\n"
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let once = format_source("{var a = \"x\ny\";\n\n\nprint a;}");
+        assert_eq!(once, "{\n    var a = \"x\ny\";\n\n    print a;\n}\n");
+        assert_eq!(format_source(&once), once);
+    }
+
+    #[test]
+    fn keeps_interpolated_strings_together() {
+        assert_eq!(
+            format_source("print \"a ${ -x+1 } b ${f( y )}\";"),
+            "print \"a ${-x + 1} b ${f(y)}\";\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments_on_their_own_lines() {
+        let source = "// header\n\n\nvar a=1;\n{\n// inside\nprint a;\n\n  /* before */\n}\n// footer\n";
+        assert_eq!(format_source(source), concat!(
+            "// header\n",
+            "\n",
+            "var a = 1;\n",
+            "{\n",
+            "    // inside\n",
+            "    print a;\n",
+            "\n",
+            "    /* before */\n",
+            "}\n",
+            "// footer\n",
+        ));
+    }
+
+    #[test]
+    fn keeps_comments_after_code() {
+        let source = "var a=1;// one\nif(a){ // open\nprint /* two */ a;}\nprint a+ // split\n1;";
+        let formatted = format_source(source);
+        assert_eq!(formatted, concat!(
+            "var a = 1; // one\n",
+            "if (a) { // open\n",
+            "    print /* two */ a;\n",
+            "}\n",
+            "print a + // split\n",
+            "1;\n",
+        ));
+        assert_eq!(format_source(&formatted), formatted);
+    }
+
+    #[test]
+    fn a_commented_empty_block_is_not_collapsed() {
+        assert_eq!(format_source("fun f() {\n// todo\n}"), "fun f() {\n    // todo\n}\n");
+        assert_eq!(format_source("fun f() { // todo\n}"), "fun f() { // todo\n}\n");
+    }
+
+    #[test]
+    fn keeps_identifiers_as_written() {
+        assert_eq!(format_source("var cafe\u{301}=1;print caf\u{e9};"), "var cafe\u{301} = 1;\nprint caf\u{e9};\n");
+    }
+}
@@ -0,0 +1,764 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::{Token, TokenType, RuntimeError};
+use crate::environment::Environment;
+use crate::expr::{Expr, ExprVisitor, Depth};
+use crate::callable::LoxCallable;
+use crate::class::LoxClass;
+use crate::function::LoxFunction;
+use crate::instance::LoxInstance;
+use crate::native::{self, NativeFunction};
+use crate::stack;
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::value::Value;
+
+type EvalResult = Result<Value, RuntimeError>;
+type ExecResult = Result<(), Unwind>;
+
+/// How deep calls may nest before a script is stopped with "Stack overflow.".
+/// The native stack grows as needed, so this bounds memory rather than
+/// protecting the thread the interpreter happens to run on.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// Reasons for statement execution to stop before reaching the end of a block.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// Where `print` writes to.
+    out: Box<dyn Write>,
+    /// How many calls are in progress.
+    call_depth: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    pub fn with_output(out: impl Write + 'static) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Interpreter { globals: globals.clone(), environment: globals, out: Box::new(out), call_depth: 0 };
+        native::define_builtins(&mut interpreter);
+        interpreter
+    }
+
+    /// Defines a global function `name` implemented by `function`. Calls with
+    /// anything other than `arity` arguments fail before `function` runs, and
+    /// errors it returns are reported at the call.
+    pub fn register_fn<F, R>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<R, RuntimeError> + 'static,
+        R: Into<Value>,
+    {
+        let function = NativeFunction::new(name, arity, function);
+        self.globals.borrow_mut().define(name, Value::Callable(Rc::new(function)));
+    }
+
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.out = Box::new(out);
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        match self.execute(statements) {
+            Err(Unwind::Error(error)) => Err(error),
+            // A `return` outside of any function just ends the program.
+            Ok(()) | Err(Unwind::Return(_)) => Ok(()),
+        }
+    }
+
+    /// Looks up a global variable by name.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get_at(0, name)
+    }
+
+    /// Calls a function, bound method or class with `arguments`, checking the
+    /// argument count first. Errors that don't come from inside the callee have
+    /// no location.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> EvalResult {
+        let function: Rc<dyn LoxCallable> = match callee {
+            Value::Callable(function) => function.clone(),
+            Value::Class(class) => class.clone(),
+            _ => return Err(RuntimeError::message("Can only call functions and classes.")),
+        };
+
+        if arguments.len() != function.arity() {
+            return Err(RuntimeError::message(
+                &format!("Expected {} arguments but got {}.", function.arity(), arguments.len()),
+            ));
+        }
+
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::message("Stack overflow."));
+        }
+
+        self.call_depth += 1;
+        let result = function.call(self, arguments);
+        self.call_depth -= 1;
+
+        result
+    }
+
+    /// Evaluates a single expression that has already been resolved.
+    pub fn evaluate(&mut self, expr: &Expr) -> EvalResult {
+        self.visit(expr)
+    }
+
+    fn execute(&mut self, statements: &[Stmt]) -> ExecResult {
+        for statement in statements {
+            self.visit_stmt(statement)?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes `statements` inside `environment`, restoring the current scope afterwards
+    /// even when a statement fails or returns.
+    pub fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> ExecResult {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = self.execute(statements);
+        self.environment = previous;
+
+        result
+    }
+
+    /// Reads a variable from the scope the resolver found for it, or from the globals.
+    fn look_up_variable(&self, name: &Token, depth: &Depth) -> EvalResult {
+        match depth.get() {
+            Some(distance) => self.environment.borrow().get_at(distance, &name.lexeme).ok_or_else(|| {
+                RuntimeError::new(name.clone(), &format!("Undefined variable '{}'.", name.lexeme))
+            }),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn number_operand(operator: &Token, operand: &Value) -> Result<f64, RuntimeError> {
+        match operand {
+            Value::Number(n) => Ok(*n),
+            _ => Err(RuntimeError::new(operator.clone(), "Operand must be a number.")),
+        }
+    }
+
+    fn number_operands(operator: &Token, left: &Value, right: &Value) -> Result<(f64, f64), RuntimeError> {
+        match (left, right) {
+            (Value::Number(l), Value::Number(r)) => Ok((*l, *r)),
+            _ => Err(RuntimeError::new(operator.clone(), "Operands must be numbers.")),
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor<EvalResult> for Interpreter {
+    fn visit(&mut self, expr: &Expr) -> EvalResult {
+        stack::grow(|| match expr {
+            Expr::Literal(ref value) => Ok(Value::from(value.clone())),
+            Expr::Grouping(ref expr) => self.visit(expr),
+            Expr::Unary(ref operator, ref right) => {
+                let right = self.visit(right)?;
+
+                match operator.kind {
+                    TokenType::MINUS => {
+                        let n = Interpreter::number_operand(operator, &right)?;
+                        Ok(Value::Number(-n))
+                    }
+                    TokenType::BANG => Ok(Value::Bool(!right.is_truthy())),
+                    _ => Err(RuntimeError::new(operator.clone(), "Unknown unary operator.")),
+                }
+            }
+            Expr::Binary(ref left, ref operator, ref right) => {
+                let left = self.visit(left)?;
+                let right = self.visit(right)?;
+
+                match operator.kind {
+                    TokenType::EQUAL_EQUAL => Ok(Value::Bool(left == right)),
+                    TokenType::BANG_EQUAL => Ok(Value::Bool(left != right)),
+                    TokenType::PLUS => match (left, right) {
+                        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+                        (Value::Str(l), Value::Str(r)) => Ok(Value::Str(l + &r)),
+                        _ => Err(RuntimeError::new(operator.clone(), "Operands must be two numbers or two strings.")),
+                    },
+                    TokenType::MINUS => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Number(l - r))
+                    }
+                    TokenType::STAR => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Number(l * r))
+                    }
+                    TokenType::SLASH => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Number(l / r))
+                    }
+                    TokenType::GREATER => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Bool(l > r))
+                    }
+                    TokenType::GREATER_EQUAL => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Bool(l >= r))
+                    }
+                    TokenType::LESS => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Bool(l < r))
+                    }
+                    TokenType::LESS_EQUAL => {
+                        let (l, r) = Interpreter::number_operands(operator, &left, &right)?;
+                        Ok(Value::Bool(l <= r))
+                    }
+                    _ => Err(RuntimeError::new(operator.clone(), "Unknown binary operator.")),
+                }
+            }
+            Expr::Variable(ref name, ref depth) => self.look_up_variable(name, depth),
+            Expr::Assign(ref name, ref value, ref depth) => {
+                let value = self.visit(value)?;
+
+                match depth.get() {
+                    Some(distance) => self.environment.borrow_mut().assign_at(distance, name, value.clone())?,
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+
+                Ok(value)
+            }
+            Expr::Logical(ref left, ref operator, ref right) => {
+                let left = self.visit(left)?;
+
+                // Short-circuit and hand back the operand itself rather than a coerced bool.
+                let short_circuits = match operator.kind {
+                    TokenType::OR => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+
+                if short_circuits { Ok(left) } else { self.visit(right) }
+            }
+            Expr::Call(ref callee, ref paren, ref arguments) => {
+                let callee = self.visit(callee)?;
+
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(self.visit(argument)?);
+                }
+
+                self.call(&callee, values).map_err(|error| error.or_at(paren))
+            }
+            Expr::Interpolation(ref parts) => {
+                // Each part reads just as `print` would show it.
+                let mut text = String::new();
+                for part in parts {
+                    text += &self.visit(part)?.to_string();
+                }
+                Ok(Value::Str(text))
+            }
+            Expr::Get(ref object, ref name) => match self.visit(object)? {
+                Value::Instance(instance) => LoxInstance::get(&instance, name),
+                _ => Err(RuntimeError::new(name.clone(), "Only instances have properties.")),
+            },
+            Expr::Set(ref object, ref name, ref value) => {
+                let instance = match self.visit(object)? {
+                    Value::Instance(instance) => instance,
+                    _ => return Err(RuntimeError::new(name.clone(), "Only instances have fields.")),
+                };
+
+                let value = self.visit(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expr::This(ref keyword, ref depth) => self.look_up_variable(keyword, depth),
+            Expr::Super(ref keyword, ref method, ref depth) => {
+                // The scope binding `this` always sits right inside the one binding `super`.
+                let distance = depth.get().unwrap_or(0);
+                let superclass = self.look_up_variable(keyword, depth)?;
+                let instance = match distance {
+                    0 => None,
+                    _ => self.environment.borrow().get_at(distance - 1, "this"),
+                }.unwrap_or(Value::Nil);
+
+                let found = match superclass {
+                    Value::Class(ref superclass) => superclass.find_method(&method.lexeme),
+                    _ => None,
+                };
+
+                match found {
+                    Some(found) => Ok(Value::Callable(Rc::new(found.bind(instance)))),
+                    None => Err(RuntimeError::new(method.clone(), &format!("Undefined property '{}'.", method.lexeme))),
+                }
+            }
+        })
+    }
+}
+
+impl StmtVisitor<ExecResult> for Interpreter {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> ExecResult {
+        stack::grow(|| match stmt {
+            Stmt::Expression(ref expr) => {
+                self.visit(expr)?;
+                Ok(())
+            }
+            Stmt::Print(ref expr) => {
+                let value = self.visit(expr)?;
+                // Like jlox's `System.out`, a failed write doesn't stop the program.
+                let _ = writeln!(self.out, "{}", value);
+                Ok(())
+            }
+            Stmt::Var(ref name, ref initializer) => {
+                let value = match initializer {
+                    Some(expr) => self.visit(expr)?,
+                    None => Value::Nil,
+                };
+
+                self.environment.borrow_mut().define(&name.lexeme, value);
+                Ok(())
+            }
+            Stmt::Block(ref statements) => {
+                let environment = Environment::with_enclosing(self.environment.clone());
+                self.execute_block(statements, environment)
+            }
+            Stmt::If(ref condition, ref then_branch, ref else_branch) => {
+                if self.visit(condition)?.is_truthy() {
+                    self.visit_stmt(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.visit_stmt(else_branch)
+                } else {
+                    Ok(())
+                }
+            }
+            Stmt::While(ref condition, ref body) => {
+                while self.visit(condition)?.is_truthy() {
+                    self.visit_stmt(body)?;
+                }
+
+                Ok(())
+            }
+            Stmt::Function(ref declaration) => {
+                let function = LoxFunction::new(declaration.clone(), self.environment.clone(), false);
+                self.environment.borrow_mut().define(&declaration.name.lexeme, Value::Callable(Rc::new(function)));
+                Ok(())
+            }
+            Stmt::Return(_, ref value) => {
+                let value = match value {
+                    Some(expr) => self.visit(expr)?,
+                    None => Value::Nil,
+                };
+
+                Err(Unwind::Return(value))
+            }
+            Stmt::Class(ref name, ref superclass, ref methods) => {
+                let superclass = match superclass {
+                    Some(expr) => match (self.visit(expr)?, &**expr) {
+                        (Value::Class(class), _) => Some(class),
+                        (_, Expr::Variable(superclass_name, _)) => {
+                            return Err(RuntimeError::new(superclass_name.clone(), "Superclass must be a class.").into());
+                        }
+                        _ => return Err(RuntimeError::new(name.clone(), "Superclass must be a class.").into()),
+                    },
+                    None => None,
+                };
+
+                self.environment.borrow_mut().define(&name.lexeme, Value::Nil);
+
+                // Methods of a subclass close over an extra scope that binds `super`.
+                let enclosing = self.environment.clone();
+                if let Some(ref superclass) = superclass {
+                    let mut environment = Environment::with_enclosing(enclosing.clone());
+                    environment.define("super", Value::Class(superclass.clone()));
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let mut class_methods = HashMap::new();
+                for method in methods {
+                    let is_initializer = method.name.lexeme == "init";
+                    let function = LoxFunction::new(method.clone(), self.environment.clone(), is_initializer);
+                    class_methods.insert(method.name.lexeme.clone(), Rc::new(function));
+                }
+
+                self.environment = enclosing;
+
+                let class = LoxClass::new(&name.lexeme, superclass, class_methods);
+                self.environment.borrow_mut().assign(name, Value::Class(Rc::new(class)))?;
+                Ok(())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::Scanner;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+
+    /// Runs `program` and then evaluates the expression `source` in the resulting state.
+    fn run_and_eval(program: &str, source: &str) -> Result<Value, String> {
+        let mut interpreter = Interpreter::new();
+
+        let mut scanner = Scanner::new(program);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+        Resolver::new().resolve(&statements).expect("resolving failed");
+        interpreter.interpret(&statements).map_err(|err| err.to_string())?;
+
+        let source = format!("{};", source);
+        let mut scanner = Scanner::new(&source);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+        Resolver::new().resolve(&statements).expect("resolving failed");
+        match &statements[..] {
+            [Stmt::Expression(expr)] => interpreter.visit(expr).map_err(|err| err.to_string()),
+            other => panic!("expected a single expression, got {:?}", other),
+        }
+    }
+
+    fn eval(source: &str) -> Result<Value, String> {
+        run_and_eval("", source)
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(eval("(1 + 2) * 4 - 6 / 3"), Ok(Value::Number(10.0)));
+        assert_eq!(eval("-(2 * 3)"), Ok(Value::Number(-6.0)));
+    }
+
+    #[test]
+    fn concatenates_strings() {
+        assert_eq!(eval("\"foo\" + \"bar\""), Ok(Value::Str(String::from("foobar"))));
+    }
+
+    #[test]
+    fn interpolates_values_as_print_shows_them() {
+        let program = "var name = \"Lox\"; class Point {} fun f() {}";
+        assert_eq!(run_and_eval(program, "\"Hello ${name}!\""), Ok(Value::Str(String::from("Hello Lox!"))));
+        assert_eq!(
+            run_and_eval(program, "\"${1 + 1} ${2.5} ${nil} ${true} ${Point} ${Point()} ${f} ${clock}\""),
+            Ok(Value::Str(String::from("2 2.5 nil true Point Point instance <fn f> <native fn clock>")))
+        );
+        assert_eq!(run_and_eval(program, "\"${\"<${name}>\"}\""), Ok(Value::Str(String::from("<Lox>"))));
+    }
+
+    #[test]
+    fn only_nil_and_false_are_falsey() {
+        assert_eq!(eval("!nil"), Ok(Value::Bool(true)));
+        assert_eq!(eval("!false"), Ok(Value::Bool(true)));
+        assert_eq!(eval("!0"), Ok(Value::Bool(false)));
+        assert_eq!(eval("!\"\""), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn compares_values_for_equality() {
+        assert_eq!(eval("nil == nil"), Ok(Value::Bool(true)));
+        assert_eq!(eval("nil == false"), Ok(Value::Bool(false)));
+        assert_eq!(eval("1 == 1"), Ok(Value::Bool(true)));
+        assert_eq!(eval("\"a\" != \"b\""), Ok(Value::Bool(true)));
+        assert_eq!(eval("3 >= 4"), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn distinguishes_string_literals_from_keywords() {
+        assert_eq!(eval("\"true\" == true"), Ok(Value::Bool(false)));
+        assert_eq!(eval("\"nil\""), Ok(Value::Str(String::from("nil"))));
+        assert_eq!(eval("nil"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn declares_and_assigns_variables() {
+        assert_eq!(run_and_eval("var a = 1; var b; a = a + 2;", "a"), Ok(Value::Number(3.0)));
+        assert_eq!(run_and_eval("var a = 1; var b;", "b"), Ok(Value::Nil));
+        assert_eq!(run_and_eval("var a = \"x\"; { a = a + \"y\"; }", "a"), Ok(Value::Str(String::from("xy"))));
+    }
+
+    #[test]
+    fn blocks_shadow_and_restore_outer_variables() {
+        let program = "var a = \"global\"; var b = 1; { var a = \"local\"; b = 2; }";
+        assert_eq!(run_and_eval(program, "a"), Ok(Value::Str(String::from("global"))));
+        assert_eq!(run_and_eval(program, "b"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn block_locals_do_not_leak() {
+        assert_eq!(
+            run_and_eval("{ var inner = 1; }", "inner"),
+            Err(String::from("[line 1] Undefined variable 'inner'."))
+        );
+    }
+
+    #[test]
+    fn uninitialized_variables_are_nil() {
+        assert_eq!(run_and_eval("var a; { var b; a = b; }", "a"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn restores_scope_after_runtime_error() {
+        let mut interpreter = Interpreter::new();
+        let mut scanner = Scanner::new("var a = 1; { var a = 2; -\"boom\"; }");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+        Resolver::new().resolve(&statements).expect("resolving failed");
+        assert!(interpreter.interpret(&statements).is_err());
+
+        let mut scanner = Scanner::new("a");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let name = tokens[0].clone();
+        assert_eq!(interpreter.environment.borrow().get(&name).unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn executes_the_matching_if_branch() {
+        assert_eq!(run_and_eval("var a; if (1 < 2) a = \"then\"; else a = \"else\";", "a"), Ok(Value::Str(String::from("then"))));
+        assert_eq!(run_and_eval("var a; if (nil) a = \"then\"; else a = \"else\";", "a"), Ok(Value::Str(String::from("else"))));
+        assert_eq!(run_and_eval("var a = 1; if (false) a = 2;", "a"), Ok(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn runs_while_loops() {
+        assert_eq!(run_and_eval("var i = 0; while (i < 5) i = i + 1;", "i"), Ok(Value::Number(5.0)));
+    }
+
+    #[test]
+    fn runs_for_loops() {
+        let program = "var sum = 0; for (var i = 1; i <= 4; i = i + 1) sum = sum + i;";
+        assert_eq!(run_and_eval(program, "sum"), Ok(Value::Number(10.0)));
+        assert_eq!(
+            run_and_eval(program, "i"),
+            Err(String::from("[line 1] Undefined variable 'i'."))
+        );
+    }
+
+    #[test]
+    fn logical_operators_return_operands() {
+        assert_eq!(eval("nil or \"yes\""), Ok(Value::Str(String::from("yes"))));
+        assert_eq!(eval("\"hi\" or 2"), Ok(Value::Str(String::from("hi"))));
+        assert_eq!(eval("nil and 2"), Ok(Value::Nil));
+        assert_eq!(eval("1 and 2"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(run_and_eval("var a = 1; true or (a = 2);", "a"), Ok(Value::Number(1.0)));
+        assert_eq!(run_and_eval("var a = 1; false and (a = 2);", "a"), Ok(Value::Number(1.0)));
+        assert_eq!(run_and_eval("var a = 1; false or (a = 2);", "a"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn calls_functions_with_arguments() {
+        let program = "fun add(a, b) { return a + b; } var sum = add(1, 2);";
+        assert_eq!(run_and_eval(program, "sum"), Ok(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn functions_without_return_produce_nil() {
+        assert_eq!(run_and_eval("fun noop() {}", "noop()"), Ok(Value::Nil));
+        assert_eq!(run_and_eval("fun early() { return; }", "early()"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn return_unwinds_nested_statements() {
+        let program = "
+            fun find() {
+                for (var i = 0; i < 10; i = i + 1) {
+                    if (i == 3) return i;
+                }
+                return -1;
+            }
+        ";
+        assert_eq!(run_and_eval(program, "find()"), Ok(Value::Number(3.0)));
+    }
+
+    #[test]
+    fn supports_recursion() {
+        let program = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }";
+        assert_eq!(run_and_eval(program, "fib(10)"), Ok(Value::Number(55.0)));
+    }
+
+    #[test]
+    fn closures_capture_their_environment() {
+        let program = "
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var counter = makeCounter();
+            var other = makeCounter();
+            counter();
+            counter();
+            other();
+        ";
+        assert_eq!(run_and_eval(program, "counter()"), Ok(Value::Number(3.0)));
+        assert_eq!(run_and_eval(program, "other()"), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn functions_are_first_class() {
+        let program = "fun twice(f, x) { return f(f(x)); } fun inc(n) { return n + 1; }";
+        assert_eq!(run_and_eval(program, "twice(inc, 5)"), Ok(Value::Number(7.0)));
+        assert_eq!(run_and_eval(program, "inc == inc"), Ok(Value::Bool(true)));
+        assert_eq!(run_and_eval(program, "inc == twice"), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn reports_bad_calls_at_the_closing_paren() {
+        assert_eq!(
+            run_and_eval("fun one(a) {}", "one(1,\n2\n)"),
+            Err(String::from("[line 3] Expected 1 arguments but got 2."))
+        );
+        assert_eq!(
+            eval("\"not a function\"(\n)"),
+            Err(String::from("[line 2] Can only call functions and classes."))
+        );
+    }
+
+    #[test]
+    fn instances_store_fields() {
+        let program = "class Bag {} var bag = Bag(); bag.apples = 3; bag.apples = bag.apples + 1;";
+        assert_eq!(run_and_eval(program, "bag.apples"), Ok(Value::Number(4.0)));
+        assert_eq!(run_and_eval(program, "bag").map(|bag| bag.to_string()), Ok(String::from("Bag instance")));
+        assert_eq!(run_and_eval(program, "Bag").map(|class| class.to_string()), Ok(String::from("Bag")));
+    }
+
+    #[test]
+    fn methods_bind_this() {
+        let program = "
+            class Person {
+                init(name) { this.name = name; }
+                greet() { return \"hi \" + this.name; }
+            }
+            var greet = Person(\"ana\").greet;
+        ";
+        assert_eq!(run_and_eval(program, "greet()"), Ok(Value::Str(String::from("hi ana"))));
+    }
+
+    #[test]
+    fn initializers_return_the_instance() {
+        let program = "
+            class Point {
+                init(x) { this.x = x; return; }
+            }
+            var p = Point(1);
+        ";
+        assert_eq!(run_and_eval(program, "p.init(5).x"), Ok(Value::Number(5.0)));
+        assert_eq!(run_and_eval(program, "p.init(5) == p"), Ok(Value::Bool(true)));
+        assert_eq!(
+            run_and_eval(program, "Point()"),
+            Err(String::from("[line 1] Expected 1 arguments but got 0."))
+        );
+    }
+
+    #[test]
+    fn inherits_and_calls_super_methods() {
+        let program = "
+            class Animal {
+                init(name) { this.name = name; }
+                speak() { return this.name + \" makes a sound\"; }
+            }
+            class Dog < Animal {
+                speak() { return super.speak() + \" (woof)\"; }
+            }
+            var dog = Dog(\"rex\");
+        ";
+        assert_eq!(run_and_eval(program, "dog.speak()"), Ok(Value::Str(String::from("rex makes a sound (woof)"))));
+    }
+
+    #[test]
+    fn super_resolves_from_the_declaring_class() {
+        let program = "
+            class A { method() { return \"A\"; } }
+            class B < A { method() { return \"B\"; } test() { return super.method(); } }
+            class C < B {}
+        ";
+        assert_eq!(run_and_eval(program, "C().test()"), Ok(Value::Str(String::from("A"))));
+    }
+
+    #[test]
+    fn reports_class_errors() {
+        assert_eq!(
+            run_and_eval("class Empty {}", "Empty().missing"),
+            Err(String::from("[line 1] Undefined property 'missing'."))
+        );
+        assert_eq!(
+            run_and_eval("var NotAClass = 1;\nclass Sub < NotAClass {}", "nil"),
+            Err(String::from("[line 2] Superclass must be a class."))
+        );
+        assert_eq!(eval("\"str\".length"), Err(String::from("[line 1] Only instances have properties.")));
+        assert_eq!(
+            run_and_eval("var n = 1;\nn.field = 2;", "nil"),
+            Err(String::from("[line 2] Only instances have fields."))
+        );
+    }
+
+    #[test]
+    fn closures_keep_their_lexical_binding() {
+        let program = "
+            var a = \"global\";
+            var first;
+            var second;
+            {
+                fun show() { return a; }
+                first = show();
+                var a = \"block\";
+                second = show();
+            }
+        ";
+        assert_eq!(run_and_eval(program, "first"), Ok(Value::Str(String::from("global"))));
+        assert_eq!(run_and_eval(program, "second"), Ok(Value::Str(String::from("global"))));
+    }
+
+    #[test]
+    fn reports_undefined_variables() {
+        assert_eq!(eval("missing"), Err(String::from("[line 1] Undefined variable 'missing'.")));
+        assert_eq!(run_and_eval("\nnope = 1;", "nil"), Err(String::from("[line 2] Undefined variable 'nope'.")));
+    }
+
+    #[test]
+    fn reports_type_errors_with_operator_line() {
+        assert_eq!(eval("-\"abc\""), Err(String::from("[line 1] Operand must be a number.")));
+        assert_eq!(eval("\n\"a\" * 2"), Err(String::from("[line 2] Operands must be numbers.")));
+        assert_eq!(eval("1 + \"a\""), Err(String::from("[line 1] Operands must be two numbers or two strings.")));
+    }
+
+    #[test]
+    fn runaway_recursion_is_a_runtime_error() {
+        assert_eq!(run_and_eval("fun f() {\n  f();\n}", "f()"), Err(String::from("[line 2] Stack overflow.")));
+    }
+
+    #[test]
+    fn the_call_depth_recovers_after_an_overflow() {
+        let mut interpreter = Interpreter::new();
+        let mut run = |source: &str| {
+            let statements = Parser::new(Scanner::new(source).scan_tokens().unwrap()).parse().unwrap();
+            Resolver::new().resolve(&statements).unwrap();
+            interpreter.interpret(&statements).map_err(|err| err.message)
+        };
+
+        run("fun f() { f(); } fun down(n) { if (n > 0) down(n - 1); }").unwrap();
+        assert_eq!(run("f();"), Err(String::from("Stack overflow.")));
+        assert_eq!(run(&format!("down({});", MAX_CALL_DEPTH - 1)), Ok(()));
+    }
+
+    #[test]
+    fn clock_is_a_builtin_native_function() {
+        assert!(matches!(eval("clock()"), Ok(Value::Number(seconds)) if seconds > 0.0));
+        assert_eq!(eval("clock").map(|clock| clock.to_string()), Ok(String::from("<native fn clock>")));
+        assert_eq!(eval("clock(1)"), Err(String::from("[line 1] Expected 0 arguments but got 1.")));
+    }
+}
@@ -0,0 +1,42 @@
+//! A tree-walking interpreter for the Lox language from *Crafting Interpreters*.
+//!
+//! [`Lox`] is the easiest way in: it runs source text and hands back values.
+//! The phases underneath, [`Scanner`], [`Parser`], [`Resolver`] and
+//! [`Interpreter`], are public too for tools that only need part of the pipeline.
+
+mod keywords;
+pub mod token;
+pub use token::*;
+pub mod token_type;
+pub use token_type::*;
+pub mod errors;
+pub use errors::*;
+pub mod span;
+pub mod diagnostic;
+pub mod render;
+pub mod json;
+pub mod emit;
+pub mod scanner;
+pub use scanner::Scanner;
+pub mod expr;
+pub use expr::Expr;
+pub mod stmt;
+pub use stmt::Stmt;
+pub mod parser;
+pub use parser::Parser;
+pub mod resolver;
+pub use resolver::Resolver;
+pub mod value;
+pub use value::Value;
+pub mod environment;
+pub mod callable;
+pub mod function;
+pub mod class;
+pub mod instance;
+pub mod native;
+pub mod interpreter;
+pub use interpreter::Interpreter;
+pub mod formatter;
+mod stack;
+mod lox;
+pub use lox::{compile, Error, Lox};
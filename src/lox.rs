@@ -0,0 +1,231 @@
+use core::fmt;
+use std::io::Write;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{Token, TokenType, ScanError, ParseError, ResolveError, RuntimeError};
+use crate::diagnostic::Diagnostic;
+use crate::instance::LoxInstance;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::span::Span;
+use crate::stmt::Stmt;
+use crate::value::Value;
+
+/// Everything that can go wrong running Lox source, grouped by the phase that
+/// caught it. The static phases report every error they find; a run stops at
+/// the first runtime error.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// The text isn't a program. The parser reads on past the scanner's
+    /// errors, so the errors of both come together, the scanner's first.
+    Syntax { scan: Vec<ScanError>, parse: Vec<ParseError> },
+    Resolve(Vec<ResolveError>),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Error::Syntax { scan, parse } => {
+                let scan = scan.iter().cloned().map(Diagnostic::from);
+                scan.chain(parse.iter().cloned().map(Diagnostic::from)).collect()
+            }
+            Error::Resolve(errors) => errors.iter().cloned().map(Into::into).collect(),
+            Error::Runtime(error) => vec![error.clone().into()],
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn lines<'a>(f: &mut fmt::Formatter, errors: impl Iterator<Item = &'a dyn fmt::Display>) -> fmt::Result {
+            for (i, error) in errors.enumerate() {
+                if i > 0 { writeln!(f)?; }
+                write!(f, "{}", error)?;
+            }
+            Ok(())
+        }
+
+        fn each<E: fmt::Display>(errors: &[E]) -> impl Iterator<Item = &dyn fmt::Display> {
+            errors.iter().map(|error| error as &dyn fmt::Display)
+        }
+
+        match self {
+            Error::Syntax { scan, parse } => lines(f, each(scan).chain(each(parse))),
+            Error::Resolve(errors) => lines(f, each(errors)),
+            Error::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+/// Scans, parses and resolves `source` without running it. The parser pulls
+/// tokens from the scanner as it goes rather than scanning everything first.
+pub fn compile(source: &str) -> Result<Vec<Stmt>, Error> {
+    let mut scan_errors = Vec::new();
+    let tokens = Scanner::new(source).filter_map(|result| result.map_err(|error| scan_errors.push(error)).ok());
+    let parsed = Parser::new(tokens).parse();
+
+    resolve(syntax(scan_errors, parsed)?)
+}
+
+/// The parsed program, unless scanning or parsing found any errors.
+fn syntax(scan: Vec<ScanError>, parsed: Result<Vec<Stmt>, Vec<ParseError>>) -> Result<Vec<Stmt>, Error> {
+    match parsed {
+        Ok(statements) if scan.is_empty() => Ok(statements),
+        Ok(_) => Err(Error::Syntax { scan, parse: Vec::new() }),
+        Err(parse) => Err(Error::Syntax { scan, parse }),
+    }
+}
+
+fn resolve(statements: Vec<Stmt>) -> Result<Vec<Stmt>, Error> {
+    Resolver::new().resolve(&statements).map_err(Error::Resolve)?;
+    Ok(statements)
+}
+
+/// An interpreter session for embedding Lox in a Rust program. Globals
+/// defined by one call are visible to the next, just like lines in the REPL.
+///
+/// ```
+/// let mut lox = rlox::Lox::new();
+/// lox.run("var greeting = \"hello\";").unwrap();
+/// assert_eq!(lox.eval("greeting + \" world\"").unwrap().to_string(), "hello world");
+/// ```
+pub struct Lox {
+    interpreter: Interpreter,
+}
+
+impl Lox {
+    /// A session whose `print` statements write to stdout.
+    pub fn new() -> Self {
+        Lox { interpreter: Interpreter::new() }
+    }
+
+    /// A session whose `print` statements write to `out`.
+    pub fn with_output(out: impl Write + 'static) -> Self {
+        Lox { interpreter: Interpreter::with_output(out) }
+    }
+
+    /// Sends the output of later `print` statements to `out`.
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.interpreter.set_output(out);
+    }
+
+    /// Makes the Rust `function` callable from Lox as the global `name`.
+    ///
+    /// ```
+    /// let mut lox = rlox::Lox::new();
+    /// lox.register_fn("double", 1, |args| Ok(f64::try_from(&args[0])? * 2.0));
+    /// assert_eq!(lox.eval("double(21)").unwrap(), rlox::Value::Number(42.0));
+    /// ```
+    pub fn register_fn<F, R>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[Value]) -> Result<R, RuntimeError> + 'static,
+        R: Into<Value>,
+    {
+        self.interpreter.register_fn(&name.nfc().collect::<String>(), arity, function);
+    }
+
+    /// Runs `source` as a program.
+    pub fn run(&mut self, source: &str) -> Result<(), Error> {
+        let statements = compile(source)?;
+        Ok(self.interpreter.interpret(&statements)?)
+    }
+
+    /// Runs `source` and returns the value of its last statement when that is
+    /// an expression, or `nil` otherwise. The final semicolon may be left out,
+    /// so `eval("1 + 2")` gives `3`.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let mut scan_errors = Vec::new();
+        let mut tokens: Vec<Token> = Scanner::new(source)
+            .filter_map(|result| result.map_err(|error| scan_errors.push(error)).ok())
+            .collect();
+        add_final_semicolon(&mut tokens);
+        let parsed = Parser::new(tokens).parse();
+
+        let mut statements = resolve(syntax(scan_errors, parsed)?)?;
+        let last = match statements.last() {
+            Some(Stmt::Expression(_)) => statements.pop(),
+            _ => None,
+        };
+
+        self.interpreter.interpret(&statements)?;
+
+        match last {
+            Some(Stmt::Expression(expr)) => Ok(self.interpreter.evaluate(&expr)?),
+            _ => Ok(Value::Nil),
+        }
+    }
+
+    /// The value of the global variable `name`, if one is defined. Like
+    /// identifiers in source, `name` is compared in NFC.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.interpreter.global(&name.nfc().collect::<String>())
+    }
+
+    /// Calls a Lox function, bound method or class with `arguments`.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, Error> {
+        Ok(self.interpreter.call(callee, arguments)?)
+    }
+
+    /// Calls the global function `name`, as Lox would for `name(arguments)`.
+    ///
+    /// ```
+    /// let mut lox = rlox::Lox::new();
+    /// lox.run("fun add(a, b) { return a + b; }").unwrap();
+    /// let sum = lox.call_function("add", vec![1.0.into(), 2.0.into()]).unwrap();
+    /// assert_eq!(sum, rlox::Value::Number(3.0));
+    /// ```
+    pub fn call_function(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+        let callee = self.global(name)
+            .ok_or_else(|| RuntimeError::message(&format!("Undefined variable '{}'.", name)))?;
+        self.call(&callee, arguments)
+    }
+
+    /// Reads the field or bound method `name` of an instance. Fields shadow
+    /// methods, just as they do for `object.name` in Lox.
+    pub fn get_property(&self, object: &Value, name: &str) -> Result<Value, Error> {
+        let Value::Instance(instance) = object else {
+            return Err(RuntimeError::message("Only instances have properties.").into());
+        };
+
+        LoxInstance::property(instance, &name.nfc().collect::<String>())
+            .ok_or_else(|| RuntimeError::message(&format!("Undefined property '{}'.", name)).into())
+    }
+
+    /// Calls the method `name` on an instance, as Lox would for `object.name(arguments)`.
+    pub fn call_method(&mut self, object: &Value, name: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+        let method = self.get_property(object, name)?;
+        self.call(&method, arguments)
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Inserts a `;` before the end of input when the last token leaves a statement open.
+fn add_final_semicolon(tokens: &mut Vec<Token>) {
+    let eof = tokens.len() - 1;
+    let needs_semicolon = eof > 0 && !matches!(tokens[eof - 1].kind, TokenType::SEMICOLON | TokenType::RIGHT_BRACE);
+
+    if needs_semicolon {
+        let last = &tokens[eof - 1];
+        let end = last.span.end_byte;
+        let semicolon = Token::new(TokenType::SEMICOLON, ";", None, last.line)
+            .with_position(Span::new(end, end), last.column + last.lexeme.chars().count());
+        tokens.insert(eof, semicolon);
+    }
+}
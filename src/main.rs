@@ -1,66 +1,214 @@
 use std::process::exit;
-use std::io::Write;
-
-mod keywords;
-pub use keywords::*;
-mod token;
-pub use token::*;
-mod token_type;
-pub use token_type::*;
-mod errors;
-pub use errors::*;
-mod scanner;
-mod expr;
-mod parser;
-
-type LoxResult = Result<(), Box<dyn std::error::Error>>;
-
-fn main() -> LoxResult {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        exit(0);
-    } else if args.len() == 2 {
-        run_file(args[1].clone())?;
-    } else {
-        run_prompt()?;
+use std::io::{IsTerminal, Write};
+
+use rlox::{Lox, Token, LiteralValue, Error};
+use rlox::diagnostic::DiagnosticSink;
+use rlox::emit::{Emitter, ErrorFormat};
+use rlox::expr::{ASTPrinter, RPNPrinter};
+use rlox::json::Json;
+use rlox::stmt::{Stmt, StmtPrinter, StmtVisitor};
+
+mod cli;
+use cli::{Command, Options};
+
+/// Exit codes from BSD's `sysexits.h`, the same ones the book's jlox uses.
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
+
+/// Why a command stopped early, which decides the exit code.
+enum Failure {
+    /// The program was rejected by the scanner, parser or resolver.
+    Static,
+    Runtime,
+    Io,
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::Static => EX_DATAERR,
+            Failure::Runtime => EX_SOFTWARE,
+            Failure::Io => EX_IOERR,
+        }
+    }
+
+    /// Records the diagnostics of `error` and classifies it.
+    fn report(error: Error, diagnostics: &mut DiagnosticSink) -> Self {
+        diagnostics.extend(error.diagnostics());
+        match error {
+            Error::Runtime(_) => Failure::Runtime,
+            Error::Syntax { .. } | Error::Resolve(_) => Failure::Static,
+        }
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(_: std::io::Error) -> Self {
+        Failure::Io
+    }
+}
+
+fn main() {
+    exit(run())
+}
+
+fn run() -> i32 {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            return EX_USAGE;
+        }
+    };
+
+    let input = match options.input {
+        Some(ref input) => input,
+        None => return run_prompt(options.error_format),
+    };
+
+    let source = match input.read() {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Could not read '{}': {}", input.name(), error);
+            return EX_IOERR;
+        }
+    };
+
+    let mut diagnostics = DiagnosticSink::new();
+    let result = run_command(&options, &mut diagnostics, &source);
+
+    let emitter = Emitter::new(options.error_format, input.name(), &source).with_color(use_color());
+    match (diagnostics.flush(&emitter), result) {
+        (Err(_), _) => EX_IOERR,
+        (Ok(()), Ok(())) => 0,
+        (Ok(()), Err(failure)) => failure.exit_code(),
     }
+}
 
-    Ok(())
+/// Colors are only worth it when a person is watching stderr.
+fn use_color() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
 }
 
-fn run_file(path: String) -> LoxResult {
-    let source = std::fs::read_to_string(path)?;
-    run(source)
+fn run_command(options: &Options, diagnostics: &mut DiagnosticSink, source: &str) -> Result<(), Failure> {
+    let mut out = std::io::stdout().lock();
+
+    match options.command {
+        Command::Run | Command::Repl => Lox::new().run(source).map_err(|error| Failure::report(error, diagnostics)),
+        Command::Tokens => {
+            // Every token is printed, ERROR tokens too, even when the scan failed.
+            for token in &scan(diagnostics, rlox::Scanner::new(source)) {
+                if options.json {
+                    writeln!(out, "{}", token_json(token))?;
+                } else {
+                    writeln!(out, "{}:{} {}", token.line, token.column, token.to_string().trim_end())?;
+                }
+            }
+            check(diagnostics)
+        }
+        Command::Ast => {
+            let tokens = scan(diagnostics, rlox::Scanner::new(source));
+            let statements = parse(diagnostics, &tokens)?;
+            for statement in &statements {
+                let printed = if options.rpn {
+                    StmtPrinter(RPNPrinter).visit_stmt(statement)
+                } else {
+                    StmtPrinter(ASTPrinter).visit_stmt(statement)
+                };
+                writeln!(out, "{}", printed)?;
+            }
+            Ok(())
+        }
+        Command::Check => match rlox::compile(source) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(Failure::report(error, diagnostics)),
+        },
+        Command::Fmt => {
+            let tokens = scan(diagnostics, rlox::Scanner::new(source).with_trivia());
+            parse(diagnostics, &tokens)?;
+            Ok(write!(out, "{}", rlox::formatter::format(&tokens))?)
+        }
+    }
 }
 
-fn run_prompt() -> LoxResult {
+fn token_json(token: &Token) -> Json {
+    let literal = match token.literal {
+        Some(LiteralValue::Number(n)) => Json::Number(n),
+        Some(LiteralValue::Str(ref s)) => Json::str(s),
+        Some(LiteralValue::Bool(b)) => Json::Bool(b),
+        Some(LiteralValue::Nil) | None => Json::Null,
+    };
+
+    Json::object([
+        ("kind", Json::str(&format!("{:?}", token.kind))),
+        ("lexeme", Json::str(&token.lexeme)),
+        ("literal", literal),
+        ("error", token.error().map_or(Json::Null, Json::str)),
+        ("line", Json::from(token.line)),
+        ("column", Json::from(token.column)),
+        ("start_byte", Json::from(token.span.start_byte)),
+        ("end_byte", Json::from(token.span.end_byte)),
+    ])
+}
+
+/// Reads and runs one line at a time. Globals survive between lines, but a
+/// failed line is forgotten as soon as its diagnostics are printed.
+fn run_prompt(error_format: ErrorFormat) -> i32 {
+    let mut lox = Lox::new();
+    let mut diagnostics = DiagnosticSink::new();
     loop {
         print!("> ");
-        std::io::stdout().flush();
+        if std::io::stdout().flush().is_err() {
+            return EX_IOERR;
+        }
+
         let mut input = String::new();
         match std::io::stdin().read_line(&mut input) {
-            Ok(read_bytes) => { 
-                if read_bytes == 0 { break; }
-                if let Err(msg) = run(input) {
-                    println!("{}", msg);
+            Ok(0) => return 0,
+            Ok(_) => {
+                // The session carries on after an error, keeping the globals defined so far.
+                // Every line gets its own report, so a clean line still writes an empty SARIF log.
+                if let Err(error) = lox.run(&input) {
+                    diagnostics.extend(error.diagnostics());
                 }
+                if diagnostics.flush(&Emitter::new(error_format, "<repl>", &input).with_color(use_color())).is_err() {
+                    return EX_IOERR;
+                }
+            }
+            Err(error) => {
+                eprintln!("Could not read from stdin: {}", error);
+                return EX_IOERR;
             }
-            _ => return Err("Could not read from stdin".into())
         }
     }
-
-    Ok(())
 }
 
-fn run(source: String) -> LoxResult {
-    let mut scanner = scanner::Scanner::new(&source);
-    let tokens = scanner.scan_tokens()?;
+/// Every token of the source, ERROR tokens included, recording the scan errors.
+fn scan(diagnostics: &mut DiagnosticSink, scanner: rlox::Scanner) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for result in scanner {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(error) => diagnostics.emit(error),
+        }
+    }
+    tokens
+}
 
-    for token in tokens.iter() {
-        println!("token: {:?}", token);
+/// Parses `tokens`, which fails if scanning them or parsing them did.
+fn parse(diagnostics: &mut DiagnosticSink, tokens: &[Token]) -> Result<Vec<Stmt>, Failure> {
+    match rlox::Parser::new(tokens.iter().cloned()).parse() {
+        Ok(statements) => check(diagnostics).map(|()| statements),
+        Err(errors) => {
+            diagnostics.extend(errors);
+            Err(Failure::Static)
+        }
     }
+}
 
-    Ok(())
+/// Fails if any error has been recorded so far.
+fn check(diagnostics: &DiagnosticSink) -> Result<(), Failure> {
+    if diagnostics.has_errors() { Err(Failure::Static) } else { Ok(()) }
 }
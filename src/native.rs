@@ -0,0 +1,65 @@
+use core::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::RuntimeError;
+use crate::callable::LoxCallable;
+use crate::interpreter::Interpreter;
+use crate::value::Value;
+
+type NativeFn = dyn Fn(&[Value]) -> Result<Value, RuntimeError>;
+
+/// A function implemented in Rust and callable from Lox. The interpreter
+/// checks the argument count before calling it, so the closure can index
+/// `arguments` freely.
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<F, R>(name: &str, arity: usize, function: F) -> Self
+    where
+        F: Fn(&[Value]) -> Result<R, RuntimeError> + 'static,
+        R: Into<Value>,
+    {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(move |arguments| function(arguments).map(Into::into)),
+        }
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(self: Rc<Self>, _interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// Defines the functions every program starts with.
+pub(crate) fn define_builtins(interpreter: &mut Interpreter) {
+    interpreter.register_fn("clock", 0, |_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs_f64())
+            .map_err(|_| RuntimeError::message("System clock is set before 1970."))
+    });
+}
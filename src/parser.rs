@@ -1,112 +1,524 @@
-use crate::{Token, expr::Expr, TokenType};
+use std::rc::Rc;
 
-struct Parser<'a> {
-    tokens: &'a [Token],
-    current: usize,
+use crate::{Token, expr::{Expr, Depth}, stmt::{Stmt, FunctionDecl}, TokenType, LiteralValue, ParseError};
+use crate::span::Span;
+use crate::stack;
+
+const MAX_ARGUMENTS: usize = 255;
+/// How deeply expressions and blocks may nest. The body of an `if`, `while`
+/// or `for` counts as a block even without braces. Every pass over the tree
+/// recurses once per level, so past this the parser gives up.
+const MAX_NESTING: usize = 256;
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// Pulls tokens one at a time as it needs them, so a parser fed straight
+/// from a `Scanner` never holds more than two tokens.
+pub struct Parser<I> {
+    tokens: I,
+    /// The next token to consume. Once `tokens` runs dry this is an EOF token,
+    /// whether or not the stream ended with one.
+    current: Token,
+    previous: Token,
+    errors: Vec<ParseError>,
+    /// How many nested expressions and blocks are being parsed.
+    depth: usize,
+    /// Set once the input nested too deeply and the rest of it was skipped.
+    gave_up: bool,
+    /// Whether ERROR tokens were skipped just before `current`.
+    after_error: bool,
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a[Token]) -> Self {
-        Self { tokens, current: 0 }
+impl<I: Iterator<Item = Token>> Parser<I> {
+    pub fn new(tokens: impl IntoIterator<IntoIter = I>) -> Self {
+        let eof = Token::new(TokenType::EOF, "", None, 1);
+        let mut parser = Self { tokens: tokens.into_iter(), current: eof.clone(), previous: eof, errors: Vec::new(), depth: 0, gave_up: false, after_error: false };
+        if let Some(first) = parser.next_token() {
+            parser.previous = first.clone();
+            parser.current = first;
+        }
+        parser
+    }
+
+    /// Parses the whole program. A syntax error doesn't stop parsing: the parser skips
+    /// ahead to the next statement and keeps going, so every error in the source is
+    /// returned together.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn declaration(&mut self) -> Option<Stmt> {
+        let result = if self.match_any(&[TokenType::CLASS]) {
+            self.class_declaration()
+        } else if self.match_any(&[TokenType::FUN]) {
+            self.function("function").map(Stmt::Function)
+        } else if self.match_any(&[TokenType::VAR]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        match result {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                // After giving up, every enclosing statement fails at the end of input
+                // too. And an error right after text the scanner rejected is most
+                // likely caused by it, so it would only repeat the scanner's error.
+                let after_error = self.after_error && error.token.span == self.current.span;
+                if !self.gave_up && !after_error {
+                    self.errors.push(error);
+                }
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expect class name.")?;
+
+        let superclass = if self.match_any(&[TokenType::LESS]) {
+            self.consume(TokenType::IDENTIFIER, "Expect superclass name.")?;
+            Some(Box::new(Expr::Variable(self.previous().clone(), Depth::default())))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(name, superclass, methods))
     }
 
-    fn expression(&mut self) -> Box<Expr> {
-        return self.equality();
+    fn function(&mut self, kind: &str) -> ParseResult<Rc<FunctionDecl>> {
+        let name = self.consume(TokenType::IDENTIFIER, &format!("Expect {} name.", kind))?;
+        self.consume(TokenType::LEFT_PAREN, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RIGHT_PAREN) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let error = self.error(self.peek(), &format!("Can't have more than {} parameters.", MAX_ARGUMENTS));
+                    self.errors.push(error);
+                }
+
+                params.push(self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?);
+                if !self.match_any(&[TokenType::COMMA]) { break; }
+            }
+        }
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
+
+        self.consume(TokenType::LEFT_BRACE, &format!("Expect '{{' before {} body.", kind))?;
+        let body = self.block()?;
+
+        Ok(Rc::new(FunctionDecl { name, params, body }))
     }
 
-    fn equality(&mut self) -> Box<Expr> {
-        let mut expr = self.comparison();
+    fn var_declaration(&mut self) -> ParseResult<Stmt> {
+        let name = self.consume(TokenType::IDENTIFIER, "Expect variable name.")?;
 
-        while self.match_any([
-            TokenType::BANG_EQUAL, 
+        let initializer = if self.match_any(&[TokenType::EQUAL]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var(name, initializer))
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        if self.match_any(&[TokenType::FOR]) { return self.for_statement(); }
+        if self.match_any(&[TokenType::IF]) { return self.if_statement(); }
+        if self.match_any(&[TokenType::PRINT]) { return self.print_statement(); }
+        if self.match_any(&[TokenType::RETURN]) { return self.return_statement(); }
+        if self.match_any(&[TokenType::WHILE]) { return self.while_statement(); }
+        if self.match_any(&[TokenType::LEFT_BRACE]) { return Ok(Stmt::Block(self.block()?)); }
+
+        self.expression_statement()
+    }
+
+    /// `for` has no node of its own: it is desugared into an equivalent `while` loop
+    /// wrapped in blocks for the initializer and the increment.
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_any(&[TokenType::SEMICOLON]) {
+            None
+        } else if self.match_any(&[TokenType::VAR]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(TokenType::SEMICOLON) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.check(TokenType::RIGHT_PAREN) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+
+        let mut body = self.body()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
+        }
+
+        let condition = condition.unwrap_or_else(|| Box::new(Expr::Literal(LiteralValue::Bool(true))));
+        body = Stmt::While(condition, Box::new(body));
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.body()?);
+        let else_branch = if self.match_any(&[TokenType::ELSE]) {
+            Some(Box::new(self.body()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(condition, then_branch, else_branch))
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.")?;
+        let body = Box::new(self.body()?);
+
+        Ok(Stmt::While(condition, body))
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().clone();
+        let value = if !self.check(TokenType::SEMICOLON) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+        let expr = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(expr))
+    }
+
+    /// The body of an `if`, `while` or `for`, which nests like a block.
+    fn body(&mut self) -> ParseResult<Stmt> {
+        self.nested(Self::statement)
+    }
+
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        self.nested(Self::nested_block)
+    }
+
+    fn nested_block(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    fn expression(&mut self) -> ParseResult<Box<Expr>> {
+        self.nested(Self::assignment)
+    }
+
+    fn assignment(&mut self) -> ParseResult<Box<Expr>> {
+        let expr = self.or()?;
+
+        if self.match_any(&[TokenType::EQUAL]) {
+            let equals = self.previous().clone();
+            let value = self.nested(Self::assignment)?;
+
+            // The parser is not confused here, so the error is recorded without unwinding.
+            return Ok(match *expr {
+                Expr::Variable(name, _) => Box::new(Expr::Assign(name, value, Depth::default())),
+                Expr::Get(object, name) => Box::new(Expr::Set(object, name, value)),
+                other => {
+                    let error = self.error(&equals, "Invalid assignment target.");
+                    self.errors.push(error);
+                    Box::new(other)
+                }
+            });
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.and()?;
+
+        while self.match_any(&[TokenType::OR]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Box::new(Expr::Logical(expr, operator, right))
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.equality()?;
+
+        while self.match_any(&[TokenType::AND]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expr = Box::new(Expr::Logical(expr, operator, right))
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.comparison()?;
+
+        while self.match_any(&[
+            TokenType::BANG_EQUAL,
             TokenType::EQUAL_EQUAL
         ]) {
-            let operator = self.previous();
-            let right = self.comparison();
+            let operator = self.previous().clone();
+            let right = self.comparison()?;
             expr = Box::new(Expr::Binary(expr, operator, right))
         }
-        
-        expr
-}
 
-    fn comparison(&mut self) -> Box<Expr> {
-        let mut expr = self.term();
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.term()?;
 
-        while self.match_any([
-            TokenType::GREATER, 
-            TokenType::GREATER_EQUAL, 
-            TokenType::LESS, 
+        while self.match_any(&[
+            TokenType::GREATER,
+            TokenType::GREATER_EQUAL,
+            TokenType::LESS,
             TokenType::LESS_EQUAL
         ]) {
-            let operator = self.previous();
-            let right = self.term();
+            let operator = self.previous().clone();
+            let right = self.term()?;
             expr = Box::new(Expr::Binary(expr, operator, right))
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Box<Expr> {
-        let mut expr = self.factor();
+    fn term(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.factor()?;
 
-        while self.match_any([
+        while self.match_any(&[
             TokenType::MINUS,
             TokenType::PLUS
         ]) {
-            let operator = self.previous();
-            let right = self.factor();
+            let operator = self.previous().clone();
+            let right = self.factor()?;
             expr = Box::new(Expr::Binary(expr, operator, right))
         }
+
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Box<Expr> {
-        let mut expr = self.unary();
+    fn factor(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.unary()?;
 
-        while self.match_any([
+        while self.match_any(&[
             TokenType::SLASH,
             TokenType::STAR
         ]) {
-            let operator = self.previous();
-            let right = self.unary();
+            let operator = self.previous().clone();
+            let right = self.unary()?;
             expr = Box::new(Expr::Binary(expr, operator, right))
         }
+
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Box<Expr> {
-        if !self.match_any([
+    fn unary(&mut self) -> ParseResult<Box<Expr>> {
+        if !self.match_any(&[
             TokenType::MINUS,
             TokenType::BANG
-        ]) { return self.primary(); }
+        ]) { return self.call(); }
+
+        let operator = self.previous().clone();
+        let right = self.nested(Self::unary)?;
+        Ok(Box::new(Expr::Unary(operator, right)))
+    }
+
+    fn call(&mut self) -> ParseResult<Box<Expr>> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_any(&[TokenType::LEFT_PAREN]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_any(&[TokenType::DOT]) {
+                let name = self.consume(TokenType::IDENTIFIER, "Expect property name after '.'.")?;
+                expr = Box::new(Expr::Get(expr, name));
+            } else {
+                break;
+            }
+        }
 
-        let token = self.previous();
-        let right = self.unary();
-        Box::new(Expr::Unary(operator, right))
+        Ok(expr)
     }
 
-    fn primary(&mut self) -> Box<Expr> {
-        if self.match_any([TokenType::FALSE]) { return Box::new(Expr::Literal(String::from("false"))); }
-        if self.match_any([TokenType::TRUE]) { return Box::new(Expr::Literal(String::from("true"))); }
-        if self.match_any([TokenType::NIL]) { return Box::new(Expr::Literal(String::from("null"))); }
+    fn finish_call(&mut self, callee: Box<Expr>) -> ParseResult<Box<Expr>> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RIGHT_PAREN) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let error = self.error(self.peek(), &format!("Can't have more than {} arguments.", MAX_ARGUMENTS));
+                    self.errors.push(error);
+                }
 
-        if self.match_any([
+                arguments.push(*self.expression()?);
+                if !self.match_any(&[TokenType::COMMA]) { break; }
+            }
+        }
+
+        let paren = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")?;
+        Ok(Box::new(Expr::Call(callee, paren, arguments)))
+    }
+
+    fn primary(&mut self) -> ParseResult<Box<Expr>> {
+        if self.match_any(&[TokenType::FALSE]) { return Ok(Box::new(Expr::Literal(LiteralValue::Bool(false)))); }
+        if self.match_any(&[TokenType::TRUE]) { return Ok(Box::new(Expr::Literal(LiteralValue::Bool(true)))); }
+        if self.match_any(&[TokenType::NIL]) { return Ok(Box::new(Expr::Literal(LiteralValue::Nil))); }
+
+        // The fragment after an interpolated expression isn't a string on its own.
+        if !self.peek().resumes_string() && self.match_any(&[
             TokenType::NUMBER,
             TokenType::STRING
         ]) {
-            return Box::new(Expr::Literal(self.previous.literal()))
+            return match self.previous().literal {
+                Some(ref literal) => Ok(Box::new(Expr::Literal(literal.clone()))),
+                None => Err(self.error(self.previous(), "Literal has no value.")),
+            };
+        }
+
+        if self.match_any(&[TokenType::INTERPOLATION]) {
+            return self.interpolation();
         }
 
-        if self.match_any([TokenType::LEFT_PAREN]) {
-            let expr = self.expression();
-            self.consume(TokenType::RIGHT_PAREN, "Expect '(' after expression,");
-            return Box::new(Expr::Grouping(expr));
+        if self.match_any(&[TokenType::SUPER]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::DOT, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::IDENTIFIER, "Expect superclass method name.")?;
+            return Ok(Box::new(Expr::Super(keyword, method, Depth::default())));
+        }
+
+        if self.match_any(&[TokenType::THIS]) {
+            return Ok(Box::new(Expr::This(self.previous().clone(), Depth::default())));
+        }
+
+        if self.match_any(&[TokenType::IDENTIFIER]) {
+            return Ok(Box::new(Expr::Variable(self.previous().clone(), Depth::default())));
+        }
+
+        if self.match_any(&[TokenType::LEFT_PAREN]) {
+            let expr = self.expression()?;
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.")?;
+            Ok(Box::new(Expr::Grouping(expr)))
         } else {
-            panic!("ERRRROOOORRR!");
+            Err(self.error(self.peek(), "Expect expression."))
+        }
+    }
+
+    /// Parses an interpolated string whose first fragment was just matched.
+    /// Fragments and embedded expressions alternate until a STRING fragment
+    /// ends the string.
+    fn interpolation(&mut self) -> ParseResult<Box<Expr>> {
+        let mut parts = Vec::new();
+        loop {
+            let fragment = self.previous();
+            match fragment.literal {
+                Some(LiteralValue::Str(ref text)) if text.is_empty() => {}
+                Some(ref literal) => parts.push(Expr::Literal(literal.clone())),
+                None => return Err(self.error(fragment, "Literal has no value.")),
+            }
+
+            if fragment.kind == TokenType::STRING {
+                return Ok(Box::new(Expr::Interpolation(parts)));
+            }
+
+            parts.push(*self.expression()?);
+            if !self.peek().resumes_string() {
+                return Err(self.error(self.peek(), "Expect '}' after interpolated expression."));
+            }
+            self.advance();
+        }
+    }
+
+    /// Parses one level deeper. Input nested past `MAX_NESTING` is reported
+    /// once, at the token that goes too deep, and everything after it is skipped.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth >= MAX_NESTING {
+            let error = self.error(self.peek(), "Too much nesting.");
+            if !self.gave_up {
+                self.gave_up = true;
+                self.errors.push(error.clone());
+                while !self.is_at_end() {
+                    self.advance();
+                }
+            }
+            return Err(error);
         }
+
+        self.depth += 1;
+        let result = stack::grow(|| parse(self));
+        self.depth -= 1;
+        result
     }
 
-    fn match_any(types: &[TokenType]) -> bool {
+    fn match_any(&mut self, types: &[TokenType]) -> bool {
         for ttype in types {
-            if self.check(ttype) {
+            if self.check(*ttype) {
                 self.advance();
                 return true;
             }
@@ -116,26 +528,414 @@ impl<'a> Parser<'a> {
         false
     }
 
-    fn consume(expected_type: TokenType, error_msg: &str) { }
+    fn consume(&mut self, expected_type: TokenType, error_msg: &str) -> ParseResult<Token> {
+        if self.check(expected_type) { return Ok(self.advance().clone()); }
+
+        Err(self.error(self.peek(), error_msg))
+    }
+
+    fn error(&self, token: &Token, message: &str) -> ParseError {
+        ParseError::new(token.clone(), message)
+    }
+
+    /// Discards tokens until the start of what is probably the next statement, so one
+    /// mistake doesn't produce a cascade of follow-up errors.
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().kind == TokenType::SEMICOLON { return; }
+
+            match self.peek().kind {
+                TokenType::CLASS
+                | TokenType::FUN
+                | TokenType::VAR
+                | TokenType::FOR
+                | TokenType::IF
+                | TokenType::WHILE
+                | TokenType::PRINT
+                | TokenType::RETURN => return,
+                _ => { self.advance(); }
+            }
+        }
+    }
 
     fn check(&self, ttype: TokenType) -> bool {
-        !self.is_at_end() && self.peek().ttype == ttype
+        !self.is_at_end() && self.peek().kind == ttype
     }
 
-    fn advance(&mut self) -> Token<'a> {
-        if !self.isAtEnd() { self.current += 1; }
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            let next = self.next_token().unwrap_or_else(|| end_of_input(&self.current));
+            self.previous = std::mem::replace(&mut self.current, next);
+        }
         self.previous()
     }
 
+    /// Pulls the next token from the stream. ERROR tokens are skipped, so
+    /// the statement around a stray character still parses. The scanner
+    /// reports what is wrong with them, not the parser.
+    fn next_token(&mut self) -> Option<Token> {
+        self.after_error = false;
+        loop {
+            let token = self.tokens.next()?;
+            if token.kind != TokenType::ERROR {
+                return Some(token);
+            }
+            self.after_error = true;
+        }
+    }
+
     fn is_at_end(&self) -> bool {
-        self.peek().ttype == TokenType::EOF
+        self.peek().kind == TokenType::EOF
+    }
+
+    fn peek(&self) -> &Token {
+        &self.current
     }
 
-    fn peek(&self) -> Token<'a> {
-        self.tokens[self.current]
+    fn previous(&self) -> &Token {
+        &self.previous
     }
+}
+
+/// An EOF token just past `last`, for a token stream that stops without one.
+fn end_of_input(last: &Token) -> Token {
+    let end = last.span.end_byte;
+    Token::new(TokenType::EOF, "", None, last.line)
+        .with_position(Span::new(end, end), last.column + last.lexeme.chars().count())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn parses_var_declaration() {
+        let mut scanner = Scanner::new("var pepe = 2;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        assert_eq!(statements.len(), 1);
+        match &statements[0] {
+            Stmt::Var(name, Some(initializer)) => {
+                assert_eq!(name.lexeme, "pepe");
+                assert_eq!(initializer.to_string(), "2");
+            }
+            other => panic!("expected a var declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_one_statement_per_semicolon() {
+        let mut scanner = Scanner::new("print 1; 2 + 3; var a;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        assert!(matches!(
+            &statements[..],
+            [Stmt::Print(_), Stmt::Expression(_), Stmt::Var(_, None)]
+        ));
+    }
+
+    #[test]
+    fn parses_nested_blocks() {
+        let mut scanner = Scanner::new("{ var a = 1; { a = 2; } }");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Block(outer)] => match &outer[..] {
+                [Stmt::Var(_, _), Stmt::Block(inner)] => match &inner[..] {
+                    [Stmt::Expression(expr)] => assert_eq!(expr.to_string(), "(= a 2)"),
+                    other => panic!("unexpected inner block {:?}", other),
+                },
+                other => panic!("unexpected outer block {:?}", other),
+            },
+            other => panic!("expected a single block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binds_else_to_the_nearest_if() {
+        let mut scanner = Scanner::new("if (a) if (b) print 1; else print 2;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::If(_, inner, None)] => assert!(matches!(**inner, Stmt::If(_, _, Some(_)))),
+            other => panic!("expected an if without else, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn desugars_for_into_while() {
+        let mut scanner = Scanner::new("for (var i = 0; i < 3; i = i + 1) print i;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Block(outer)] => match &outer[..] {
+                [Stmt::Var(name, _), Stmt::While(condition, body)] => {
+                    assert_eq!(name.lexeme, "i");
+                    assert_eq!(condition.to_string(), "(< i 3)");
+                    match &**body {
+                        Stmt::Block(inner) => match &inner[..] {
+                            [Stmt::Print(_), Stmt::Expression(increment)] => {
+                                assert_eq!(increment.to_string(), "(= i (+ i 1))")
+                            }
+                            other => panic!("unexpected loop body {:?}", other),
+                        },
+                        other => panic!("expected a block body, got {:?}", other),
+                    }
+                }
+                other => panic!("unexpected desugared block {:?}", other),
+            },
+            other => panic!("expected a single block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn for_without_clauses_loops_forever() {
+        let mut scanner = Scanner::new("for (;;) print 1;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::While(condition, body)] => {
+                assert_eq!(condition.to_string(), "true");
+                assert!(matches!(**body, Stmt::Print(_)));
+            }
+            other => panic!("expected a bare while loop, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let mut scanner = Scanner::new("a or b and c;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Expression(expr)] => assert_eq!(expr.to_string(), "(or a (and b c))"),
+            other => panic!("expected an expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_function_declarations() {
+        let mut scanner = Scanner::new("fun add(a, b) { return a + b; }");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
 
-    fn previous(&self) -> Token<'a> {
-        self.tokens[self.current - 1]
+        match &statements[..] {
+            [Stmt::Function(declaration)] => {
+                assert_eq!(declaration.name.lexeme, "add");
+                let params: Vec<&str> = declaration.params.iter().map(|param| param.lexeme.as_str()).collect();
+                assert_eq!(params, ["a", "b"]);
+                assert!(matches!(&declaration.body[..], [Stmt::Return(_, Some(_))]));
+            }
+            other => panic!("expected a function declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_chained_calls() {
+        let mut scanner = Scanner::new("make(1)(2, 3)();");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Expression(expr)] => assert_eq!(expr.to_string(), "(call (call (call make 1) 2 3))"),
+            other => panic!("expected an expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_class_declarations() {
+        let mut scanner = Scanner::new("class B < A { init(x) { this.x = x; } get() { return super.get(); } }");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Class(name, Some(superclass), methods)] => {
+                assert_eq!(name.lexeme, "B");
+                assert_eq!(superclass.to_string(), "A");
+                let names: Vec<&str> = methods.iter().map(|method| method.name.lexeme.as_str()).collect();
+                assert_eq!(names, ["init", "get"]);
+                match &methods[0].body[..] {
+                    [Stmt::Expression(expr)] => assert_eq!(expr.to_string(), "(= (. this x) x)"),
+                    other => panic!("unexpected initializer body {:?}", other),
+                }
+            }
+            other => panic!("expected a class declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_property_chains() {
+        let mut scanner = Scanner::new("a.b(1).c = 2;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Expression(expr)] => assert_eq!(expr.to_string(), "(= (. (call (. a b) 1) c) 2)"),
+            other => panic!("expected an expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_interpolated_strings() {
+        let mut scanner = Scanner::new(r#"print "a${1 + 2}b${"${c}"}";"#);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Print(expr)] => assert_eq!(expr.to_string(), "(interpolate a (+ 1 2) b (interpolate c))"),
+            other => panic!("expected a print statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pulls_tokens_straight_from_the_scanner() {
+        let tokens = Scanner::new("var a = 1; print a;").map(|token| token.expect("scanning failed"));
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        assert_eq!(statements.len(), 2);
+    }
+
+    #[test]
+    fn a_stream_without_eof_ends_where_its_tokens_do() {
+        let mut scanner = Scanner::new("print 1");
+        let tokens: Vec<Token> = scanner.scan_tokens().expect("scanning failed").into_iter()
+            .filter(|token| token.kind != TokenType::EOF)
+            .collect();
+
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(errors[0].to_string(), "[line 1] Error at end: Expect ';' after value.");
+        assert_eq!(errors[0].token.span, Span::new(7, 7));
+
+        assert!(Parser::new(Vec::new()).parse().unwrap().is_empty());
+    }
+
+    fn parse_errors(source: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+
+        match Parser::new(tokens).parse() {
+            Ok(statements) => panic!("expected {:?} to fail parsing, got {:?}", source, statements),
+            Err(errors) => errors.iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_errors_at_the_offending_token() {
+        assert_eq!(parse_errors("(1 + 2;"), ["[line 1] Error at ';': Expect ')' after expression."]);
+        assert_eq!(parse_errors("print 1"), ["[line 1] Error at end: Expect ';' after value."]);
+        assert_eq!(parse_errors("var = 1;"), ["[line 1] Error at '=': Expect variable name."]);
+        assert_eq!(parse_errors("1 + ;"), ["[line 1] Error at ';': Expect expression."]);
+    }
+
+    #[test]
+    fn reports_bad_interpolated_expressions() {
+        assert_eq!(parse_errors(r#"print "${}";"#), [r#"[line 1] Error at '}"': Expect expression."#]);
+        assert_eq!(parse_errors(r#"print "${a b}";"#), ["[line 1] Error at 'b': Expect '}' after interpolated expression."]);
+    }
+
+    #[test]
+    fn skips_error_tokens_without_reporting_them() {
+        let tokens = Scanner::new("var a = 1 @;\nprint a;\nprint \"oops;").filter_map(Result::ok);
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        // The last statement is lost, but the scanner has already said why.
+        assert_eq!(statements.len(), 2);
+    }
+
+    #[test]
+    fn errors_that_do_not_follow_error_tokens_are_still_reported() {
+        let tokens = Scanner::new("print \"a\\q\";\nprint 1 @ 2;\nprint (;").filter_map(Result::ok);
+        let errors: Vec<String> = Parser::new(tokens).parse().unwrap_err().iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(errors, ["[line 3] Error at ';': Expect expression."]);
+    }
+
+    #[test]
+    fn reports_every_syntax_error_in_one_pass() {
+        let source = "var a = ;\nprint a\nvar b = 2;\nfun (x) {}\nprint b;";
+        assert_eq!(parse_errors(source), [
+            "[line 1] Error at ';': Expect expression.",
+            "[line 3] Error at 'var': Expect ';' after value.",
+            "[line 4] Error at '(': Expect function name.",
+        ]);
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let source = "{\n  print ;\n  var ok = 1;\n}\nprint );";
+        assert_eq!(parse_errors(source), [
+            "[line 2] Error at ';': Expect expression.",
+            "[line 5] Error at ')': Expect expression.",
+        ]);
+    }
+
+    #[test]
+    fn invalid_assignment_targets_do_not_stop_parsing() {
+        assert_eq!(parse_errors("1 = 2; a + b = 3; print 1;"), [
+            "[line 1] Error at '=': Invalid assignment target.",
+            "[line 1] Error at '=': Invalid assignment target.",
+        ]);
+    }
+
+    #[test]
+    fn assignment_is_right_associative() {
+        let mut scanner = Scanner::new("a = b = 3;");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        match &statements[..] {
+            [Stmt::Expression(expr)] => assert_eq!(expr.to_string(), "(= a (= b 3))"),
+            other => panic!("expected an expression statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_reported_once() {
+        let parens = format!("print {}1{};\nprint 2;", "(".repeat(20000), ")".repeat(20000));
+        assert_eq!(parse_errors(&parens), ["[line 1] Error at '(': Too much nesting."]);
+
+        let negations = format!("print {}1;", "-".repeat(20000));
+        assert_eq!(parse_errors(&negations), ["[line 1] Error at '-': Too much nesting."]);
+
+        let blocks = format!("{}{}", "{".repeat(20000), "}".repeat(20000));
+        assert_eq!(parse_errors(&blocks), ["[line 1] Error at '{': Too much nesting."]);
+
+        let ifs = format!("{}print 1;", "if (true) ".repeat(20000));
+        assert_eq!(parse_errors(&ifs), ["[line 1] Error at 'true': Too much nesting."]);
+    }
+
+    #[test]
+    fn nesting_up_to_the_limit_parses() {
+        let parses = |source: String| Parser::new(Scanner::new(&source).scan_tokens().unwrap()).parse().is_ok();
+
+        // The expression itself is one level, and each pair of parentheses another.
+        assert!(parses(format!("print {}1{};", "(".repeat(MAX_NESTING - 1), ")".repeat(MAX_NESTING - 1))));
+        assert!(!parses(format!("print {}1{};", "(".repeat(MAX_NESTING), ")".repeat(MAX_NESTING))));
+
+        assert!(parses(format!("{}print 1;{}", "{".repeat(MAX_NESTING - 1), "}".repeat(MAX_NESTING - 1))));
+        assert!(!parses(format!("{}print 1;{}", "{".repeat(MAX_NESTING), "}".repeat(MAX_NESTING))));
+    }
+
+    #[test]
+    fn literal_tokens_without_a_value_are_errors() {
+        for kind in [TokenType::NUMBER, TokenType::STRING, TokenType::INTERPOLATION] {
+            let tokens = [
+                Token::new(TokenType::PRINT, "print", None, 1),
+                Token::new(kind, "x", None, 1),
+                Token::new(TokenType::SEMICOLON, ";", None, 1),
+            ];
+            let errors = Parser::new(tokens).parse().unwrap_err();
+            assert_eq!(errors[0].to_string(), "[line 1] Error at 'x': Literal has no value.");
+        }
     }
 }
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::diagnostic::{Diagnostic, Location, Severity};
+use crate::span::LineIndex;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// One underline drawn beneath a single source line.
+struct Underline<'d> {
+    start_column: usize,
+    end_column: usize,
+    primary: bool,
+    /// Printed after the underline; only set on the last line an annotation covers.
+    message: Option<&'d str>,
+}
+
+/// Renders diagnostics the way a person wants to read them: a header with the
+/// error code, the file and position, the source lines involved with the
+/// primary span underlined by `^` and every secondary label by `-`.
+pub struct Renderer<'a> {
+    file_name: &'a str,
+    line_index: LineIndex<'a>,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(file_name: &'a str, source: &'a str) -> Self {
+        Renderer { file_name, line_index: LineIndex::new(source), color: false }
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let severity_style = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        };
+
+        let header = format!("{}[{}]", diagnostic.severity.to_string().to_lowercase(), diagnostic.code);
+        let _ = writeln!(out, "{}{}", self.paint(severity_style, &header), self.paint(BOLD, &format!(": {}", diagnostic.message)));
+
+        let mut annotations: Vec<(&Location, bool, Option<&str>)> = Vec::new();
+        if let Some(ref location) = diagnostic.location {
+            annotations.push((location, true, None));
+        }
+        for label in &diagnostic.labels {
+            annotations.push((&label.location, false, Some(&label.message)));
+        }
+
+        let mut lines: BTreeMap<usize, Vec<Underline>> = BTreeMap::new();
+        for &(location, primary, message) in &annotations {
+            self.underline(location, primary, message, &mut lines);
+        }
+
+        let gutter_width = lines.keys().next_back().map_or(0, |line| line.to_string().len());
+        let gutter = " ".repeat(gutter_width);
+
+        if let Some(&(location, _, _)) = annotations.first() {
+            let _ = writeln!(out, "{}{} {}:{}:{}", gutter, self.paint(BLUE, "-->"), self.file_name, location.line, location.column);
+            let _ = writeln!(out, "{} {}", gutter, self.paint(BLUE, "|"));
+        }
+
+        let mut previous_line = None;
+        for (&line, underlines) in &mut lines {
+            if previous_line.is_some_and(|previous| line > previous + 1) {
+                let _ = writeln!(out, "{}", self.paint(BLUE, "..."));
+            }
+            previous_line = Some(line);
+
+            let text = self.line_index.line_text(line).unwrap_or("");
+            let number = format!("{:>width$} |", line, width = gutter_width);
+            let row = format!("{} {}", self.paint(BLUE, &number), text);
+            let _ = writeln!(out, "{}", row.trim_end());
+
+            underlines.sort_by_key(|underline| !underline.primary);
+            for underline in underlines.iter() {
+                let (marker, style) = if underline.primary { ('^', severity_style) } else { ('-', BLUE) };
+                let width = underline.end_column.saturating_sub(underline.start_column).max(1);
+                let mut mark = marker.to_string().repeat(width);
+                if let Some(message) = underline.message {
+                    mark.push(' ');
+                    mark.push_str(message);
+                }
+
+                let _ = writeln!(
+                    out,
+                    "{} {} {}{}",
+                    gutter,
+                    self.paint(BLUE, "|"),
+                    padding(text, underline.start_column),
+                    self.paint(style, &mark),
+                );
+            }
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "{} {} note: {}", gutter, self.paint(BLUE, "="), note);
+        }
+
+        out
+    }
+
+    /// Splits the span of `location` into one underline per source line it covers.
+    fn underline<'d>(&self, location: &Location, primary: bool, message: Option<&'d str>, lines: &mut BTreeMap<usize, Vec<Underline<'d>>>) {
+        let (start_line, start_column) = self.line_index.line_column(location.span.start_byte);
+        let (mut end_line, mut end_column) = self.line_index.line_column(location.span.end_byte);
+
+        // A span that stops right after a newline ends on the line before.
+        if end_line > start_line && end_column == 1 {
+            end_line -= 1;
+            end_column = self.line_length(end_line) + 1;
+        }
+
+        for line in start_line..=end_line {
+            let from = if line == start_line { start_column } else { 1 };
+            let to = if line == end_line { end_column } else { self.line_length(line) + 1 };
+
+            lines.entry(line).or_default().push(Underline {
+                start_column: from,
+                end_column: to,
+                primary,
+                message: if line == end_line { message } else { None },
+            });
+        }
+    }
+
+    fn line_length(&self, line: usize) -> usize {
+        self.line_index.line_text(line).map_or(0, |text| text.chars().count())
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Whitespace that lines up with `column` of `text`, keeping tabs so the
+/// underline sits under the right character however tabs are displayed.
+fn padding(text: &str, column: usize) -> String {
+    let mut chars = text.chars();
+    (1..column)
+        .map(|_| match chars.next() {
+            Some('\t') => '\t',
+            _ => ' ',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostic::{codes, Label};
+    use crate::span::Span;
+
+    fn location(source: &str, start_byte: usize, end_byte: usize) -> Location {
+        let (line, column) = LineIndex::new(source).line_column(start_byte);
+        Location { span: Span::new(start_byte, end_byte), line, column, text: source[start_byte..end_byte].to_string() }
+    }
+
+    #[test]
+    fn underlines_the_primary_span() {
+        let source = "var a = 1;\nprint a +;\n";
+        let diagnostic = Diagnostic::error(codes::PARSE_ERROR, "Expect expression.")
+            .with_location(location(source, 20, 21));
+
+        assert_eq!(Renderer::new("test.lox", source).render(&diagnostic), concat!(
+            "error[E0100]: Expect expression.\n",
+            " --> test.lox:2:10\n",
+            "  |\n",
+            "2 | print a +;\n",
+            "  |          ^\n",
+        ));
+    }
+
+    #[test]
+    fn draws_secondary_labels_and_notes() {
+        let source = "{\n  var a;\n  var a;\n}";
+        let mut diagnostic = Diagnostic::error(codes::RESOLVE_ERROR, "Already a variable with this name in this scope.")
+            .with_location(location(source, 17, 18));
+        diagnostic.labels.push(Label::new(location(source, 8, 9), "previous declaration here"));
+        diagnostic.notes.push(String::from("shadow it in a new block instead"));
+
+        assert_eq!(Renderer::new("test.lox", source).render(&diagnostic), concat!(
+            "error[E0200]: Already a variable with this name in this scope.\n",
+            " --> test.lox:3:7\n",
+            "  |\n",
+            "2 |   var a;\n",
+            "  |       - previous declaration here\n",
+            "3 |   var a;\n",
+            "  |       ^\n",
+            "  = note: shadow it in a new block instead\n",
+        ));
+    }
+
+    #[test]
+    fn marks_skipped_lines_and_multiline_spans() {
+        let source = "x;\n\n\nprint \"a\nbc\";";
+        let mut diagnostic = Diagnostic::error(codes::RUNTIME_ERROR, "Bad string.")
+            .with_location(location(source, 11, 17));
+        diagnostic.labels.push(Label::new(location(source, 0, 1), "used here"));
+
+        assert_eq!(Renderer::new("test.lox", source).render(&diagnostic), concat!(
+            "error[E0300]: Bad string.\n",
+            " --> test.lox:4:7\n",
+            "  |\n",
+            "1 | x;\n",
+            "  | - used here\n",
+            "...\n",
+            "4 | print \"a\n",
+            "  |       ^^\n",
+            "5 | bc\";\n",
+            "  | ^^^\n",
+        ));
+    }
+
+    #[test]
+    fn points_past_the_last_character_at_end_of_input() {
+        let source = "print 1";
+        let diagnostic = Diagnostic::error(codes::PARSE_ERROR, "Expect ';' after value.")
+            .with_location(location(source, 7, 7));
+
+        assert!(Renderer::new("test.lox", source).render(&diagnostic).ends_with("1 | print 1\n  |        ^\n"));
+    }
+
+    #[test]
+    fn keeps_tabs_and_counts_characters() {
+        let source = "\tprint \"é\" @;";
+        let diagnostic = Diagnostic::error(codes::SCAN_ERROR, "Unexpected character.")
+            .with_location(location(source, 12, 13));
+
+        assert!(Renderer::new("test.lox", source).render(&diagnostic).ends_with("  | \t          ^\n"));
+    }
+
+    #[test]
+    fn colors_only_when_asked() {
+        let diagnostic = Diagnostic::error(codes::SCAN_ERROR, "Oops.");
+
+        assert_eq!(Renderer::new("test.lox", "").render(&diagnostic), "error[E0001]: Oops.\n");
+        assert_eq!(
+            Renderer::new("test.lox", "").with_color(true).render(&diagnostic),
+            "\x1b[1;31merror[E0001]\x1b[0m\x1b[1m: Oops.\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use crate::{Token, ResolveError};
+use crate::diagnostic::Location;
+use crate::expr::{Expr, ExprVisitor, Depth};
+use crate::stmt::{Stmt, StmtVisitor, FunctionDecl};
+use crate::stack;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Binding {
+    /// `true` once the variable's initializer is done.
+    defined: bool,
+    /// Where the name was declared; `None` for the implicit `this` and `super`.
+    declared_at: Option<Location>,
+}
+
+impl Binding {
+    fn implicit() -> Self {
+        Binding { defined: true, declared_at: None }
+    }
+}
+
+/// Static pass that runs between parsing and interpretation. It records on every
+/// variable expression how many scopes up its binding lives and reports the
+/// mistakes that can be caught without running the program.
+pub struct Resolver {
+    /// One map per local scope.
+    scopes: Vec<HashMap<String, Binding>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<ResolveError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(mut self, statements: &[Stmt]) -> Result<(), Vec<ResolveError>> {
+        self.resolve_statements(statements);
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.visit_stmt(statement);
+        }
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, kind: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = kind;
+
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&declaration.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, name: &Token, depth: &Depth) {
+        let found = self.scopes.iter().rev().position(|scope| scope.contains_key(&name.lexeme));
+        depth.set(found);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let binding = Binding { defined: false, declared_at: Some(Location::from(name)) };
+        let previous = match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.lexeme.clone(), binding),
+            None => None,
+        };
+
+        if let Some(previous) = previous {
+            let mut error = ResolveError::new(name.clone(), "Already a variable with this name in this scope.");
+            if let Some(declared_at) = previous.declared_at {
+                error = error.with_label(declared_at, "previous declaration here");
+            }
+            self.errors.push(error);
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(binding) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.lexeme)) {
+            binding.defined = true;
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(ResolveError::new(token.clone(), message));
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        stack::grow(|| match stmt {
+            Stmt::Expression(ref expr) | Stmt::Print(ref expr) => self.visit(expr),
+            Stmt::Var(ref name, ref initializer) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.visit(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Block(ref statements) => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::If(ref condition, ref then_branch, ref else_branch) => {
+                self.visit(condition);
+                self.visit_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.visit_stmt(else_branch);
+                }
+            }
+            Stmt::While(ref condition, ref body) => {
+                self.visit(condition);
+                self.visit_stmt(body);
+            }
+            Stmt::Function(ref declaration) => {
+                // Defined before the body is resolved so the function can call itself.
+                self.declare(&declaration.name);
+                self.define(&declaration.name);
+                self.resolve_function(declaration, FunctionType::Function);
+            }
+            Stmt::Return(ref keyword, ref value) => {
+                if self.current_function == FunctionType::None {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.visit(value);
+                }
+            }
+            Stmt::Class(ref name, ref superclass, ref methods) => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable(ref superclass_name, _) = **superclass {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(superclass_name, "A class can't inherit from itself.");
+                        }
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.visit(superclass);
+
+                    self.begin_scope();
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(String::from("super"), Binding::implicit());
+                    }
+                }
+
+                self.begin_scope();
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(String::from("this"), Binding::implicit());
+                }
+
+                for method in methods {
+                    let kind = if method.name.lexeme == "init" {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, kind);
+                }
+
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+        })
+    }
+}
+
+impl ExprVisitor<()> for Resolver {
+    fn visit(&mut self, expr: &Expr) {
+        stack::grow(|| match expr {
+            Expr::Literal(_) => {}
+            Expr::Grouping(ref expr) | Expr::Unary(_, ref expr) => self.visit(expr),
+            Expr::Binary(ref left, _, ref right) | Expr::Logical(ref left, _, ref right) => {
+                self.visit(left);
+                self.visit(right);
+            }
+            Expr::Variable(ref name, ref depth) => {
+                let in_own_initializer = self.scopes.last()
+                    .and_then(|scope| scope.get(&name.lexeme))
+                    .is_some_and(|binding| !binding.defined);
+
+                if in_own_initializer {
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+
+                self.resolve_local(name, depth);
+            }
+            Expr::Assign(ref name, ref value, ref depth) => {
+                self.visit(value);
+                self.resolve_local(name, depth);
+            }
+            Expr::Call(ref callee, _, ref arguments) => {
+                self.visit(callee);
+                for argument in arguments {
+                    self.visit(argument);
+                }
+            }
+            Expr::Interpolation(ref parts) => {
+                for part in parts {
+                    self.visit(part);
+                }
+            }
+            Expr::Get(ref object, _) => self.visit(object),
+            Expr::Set(ref object, _, ref value) => {
+                self.visit(value);
+                self.visit(object);
+            }
+            Expr::This(ref keyword, ref depth) => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+
+                self.resolve_local(keyword, depth);
+            }
+            Expr::Super(ref keyword, _, ref depth) => {
+                match self.current_class {
+                    ClassType::None => self.error(keyword, "Can't use 'super' outside of a class."),
+                    ClassType::Class => self.error(keyword, "Can't use 'super' in a class with no superclass."),
+                    ClassType::Subclass => {}
+                }
+
+                self.resolve_local(keyword, depth);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::Scanner;
+    use crate::parser::Parser;
+
+    fn resolve(source: &str) -> Result<(), Vec<String>> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+
+        Resolver::new().resolve(&statements)
+            .map_err(|errors| errors.iter().map(|error| error.to_string()).collect())
+    }
+
+    fn resolve_error(source: &str) -> String {
+        match resolve(source) {
+            Ok(()) => panic!("expected {:?} to fail resolving", source),
+            Err(errors) => errors.join("\n"),
+        }
+    }
+
+    #[test]
+    fn records_scope_depths() {
+        let mut scanner = Scanner::new("var g; { var a; { a; g; } }");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+        Resolver::new().resolve(&statements).expect("resolving failed");
+
+        let inner = match &statements[..] {
+            [_, Stmt::Block(outer)] => match &outer[..] {
+                [_, Stmt::Block(inner)] => inner,
+                other => panic!("unexpected outer block {:?}", other),
+            },
+            other => panic!("unexpected program {:?}", other),
+        };
+
+        match &inner[..] {
+            [Stmt::Expression(local), Stmt::Expression(global)] => {
+                assert!(matches!(**local, Expr::Variable(_, ref depth) if depth.get() == Some(1)));
+                assert!(matches!(**global, Expr::Variable(_, ref depth) if depth.get().is_none()));
+            }
+            other => panic!("unexpected inner block {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_programs() {
+        assert_eq!(resolve("var a = 1; { var b = a; fun f(x) { return x + b; } }"), Ok(()));
+        assert_eq!(resolve("class A { init() { return; } m() { return this; } } class B < A { m() { return super.m(); } }"), Ok(()));
+        assert_eq!(resolve("var a = 1; var a = 2;"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_reading_local_in_its_own_initializer() {
+        assert_eq!(
+            resolve_error("{ var a = a; }"),
+            "[line 1] Error at 'a': Can't read local variable in its own initializer."
+        );
+    }
+
+    #[test]
+    fn rejects_redeclaring_locals() {
+        assert_eq!(
+            resolve_error("fun f(a) { var a; }"),
+            "[line 1] Error at 'a': Already a variable with this name in this scope."
+        );
+    }
+
+    #[test]
+    fn points_back_at_the_previous_declaration() {
+        let mut scanner = Scanner::new("{ var a;\n  var a; }");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+        let errors = Resolver::new().resolve(&statements).unwrap_err();
+
+        assert_eq!(errors[0].token.line, 2);
+        assert_eq!(errors[0].labels[0].message, "previous declaration here");
+        assert_eq!((errors[0].labels[0].location.line, errors[0].labels[0].location.column), (1, 7));
+    }
+
+    #[test]
+    fn rejects_top_level_return() {
+        assert_eq!(resolve_error("return 1;"), "[line 1] Error at 'return': Can't return from top-level code.");
+    }
+
+    #[test]
+    fn rejects_this_outside_of_a_class() {
+        assert_eq!(resolve_error("fun f() { this; }"), "[line 1] Error at 'this': Can't use 'this' outside of a class.");
+    }
+
+    #[test]
+    fn rejects_invalid_super() {
+        assert_eq!(resolve_error("super.m();"), "[line 1] Error at 'super': Can't use 'super' outside of a class.");
+        assert_eq!(
+            resolve_error("class A { m() { super.m(); } }"),
+            "[line 1] Error at 'super': Can't use 'super' in a class with no superclass."
+        );
+        assert_eq!(resolve_error("class A < A {}"), "[line 1] Error at 'A': A class can't inherit from itself.");
+    }
+
+    #[test]
+    fn rejects_returning_a_value_from_init() {
+        assert_eq!(
+            resolve_error("class A { init() { return 1; } }"),
+            "[line 1] Error at 'return': Can't return a value from an initializer."
+        );
+    }
+
+    #[test]
+    fn reports_every_error() {
+        assert_eq!(resolve("return;\nthis;").unwrap_err().len(), 2);
+    }
+}
@@ -1,231 +1,583 @@
-use std::str::Chars;
-use std::iter::Peekable;
+use std::collections::VecDeque;
 
-use crate::{TokenType, LoxResult, errors, keywords};
+use unicode_ident::{is_xid_continue, is_xid_start};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+use crate::{TokenType, LiteralValue, ScanError, Trivia, TriviaKind, keywords};
+use crate::token::TokenExtra;
+use crate::diagnostic::Location;
+use crate::span::Span;
 
 use super::Token;
 
+/// Turns source text into tokens. Positions are tracked as byte offsets into
+/// `source`, so slicing out a lexeme is always on a char boundary.
+///
+/// The scanner is an iterator that scans tokens as they are asked for. Errors
+/// come out in source order among the tokens, and scanning carries on after
+/// them; the last item is always an EOF token. The text each error is
+/// about, like a stray character or a string with a bad escape, comes out
+/// as an ERROR token after it, so every byte of the source is accounted for.
+/// The errors are the report: the parser skips ERROR tokens silently.
+///
+/// Whitespace and comments are skipped unless the scanner is made
+/// [`with_trivia`](Scanner::with_trivia), in which case they are attached to
+/// the tokens around them.
+///
+/// ```
+/// use rlox::{Scanner, TokenType};
+///
+/// let kinds: Vec<_> = Scanner::new("print 1;").map(|token| token.unwrap().kind).collect();
+/// assert_eq!(kinds, [TokenType::PRINT, TokenType::NUMBER, TokenType::SEMICOLON, TokenType::EOF]);
+/// ```
 pub struct Scanner<'a> {
     source: &'a str,
-    tokens: Vec<Token<'a>>,
+    /// Tokens and errors from the last token scanned, not yet handed out.
+    pending: VecDeque<Result<Token, ScanError>>,
+    /// Set once the EOF token has been queued.
+    finished: bool,
+    keep_trivia: bool,
+    /// Trivia scanned since the last token, waiting to lead the next one.
+    leading_trivia: Vec<Trivia>,
+    /// Byte offset of the first character of the token being scanned.
     start: usize,
-    at: usize,
-    current: Chars<'a>,
+    /// Byte offset of the next character to consume.
+    current: usize,
     line: usize,
-    failed: bool,
+    /// Column of the next character to consume, counted in characters.
+    column: usize,
+    /// Line and column where the token being scanned starts.
+    start_line: usize,
+    start_column: usize,
+    /// The first error reported in the token being scanned, which makes it an ERROR token.
+    token_error: Option<String>,
+    /// For each interpolated expression being scanned, innermost last, how
+    /// many of its `{` are still open. A `}` with none open resumes the string.
+    interpolations: Vec<usize>,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Scanner<'a> {
         Scanner {
             source,
-            tokens: Vec::new(),
+            pending: VecDeque::new(),
+            finished: false,
+            keep_trivia: false,
+            leading_trivia: Vec::new(),
             start: 0,
-            at: 0,
-            current: source.chars(),
+            current: 0,
             line: 1,
-            failed: false,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            token_error: None,
+            interpolations: Vec::new(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<&'a[Token], Box<dyn std::error::Error>> {
-        while !self.is_at_end() {
-            self.scan_token()
+    /// Keeps whitespace and comments as trivia on the tokens, so that the
+    /// tokens' [`full_text`](Token::full_text) adds up to the source again.
+    /// A token takes the trivia after it up to the end of its line; the
+    /// rest, newline included, leads the next token.
+    pub fn with_trivia(mut self) -> Self {
+        self.keep_trivia = true;
+        self
+    }
+
+    /// Scans the rest of the source, reporting every lexical error rather than just the first.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<ScanError>> {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
         }
-        
-        if self.failed {
-            return Err("Scanning Failed".into())
-        } 
-        
-        Ok(&self.tokens)
     }
 
-    fn is_at_end(&mut self) -> bool { 
-        self.peek_first_char().is_none()
+    fn is_at_end(&self) -> bool {
+        self.current >= self.source.len()
     }
 
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.token_error = None;
+    }
 
     fn peek_and_add_token(&mut self, expecting: char, matched: TokenType, not_matched: TokenType) {
         if self.match_next_char(expecting) {
-            self.add_token(self.at-1, matched, None);
+            self.add_token(matched, None);
         } else {
-            self.add_token(self.at-1, not_matched, None);
+            self.add_token(not_matched, None);
         }
     }
 
     fn scan_token(&mut self) {
-        let c = self.advance(); 
+        let c = self.advance();
         match c {
-            '(' => self.add_token(self.at-1, TokenType::LEFT_PAREN, None),
-            ')' => self.add_token(self.at-1, TokenType::RIGHT_PAREN, None),
-            '{' => self.add_token(self.at-1, TokenType::LEFT_BRACE, None),
-            '}' => self.add_token(self.at-1, TokenType::RIGHT_BRACE, None),
-            ',' => self.add_token(self.at-1, TokenType::COMMA, None),
-            '.' => self.add_token(self.at-1, TokenType::DOT, None),
-            '-' => self.add_token(self.at-1, TokenType::MINUS, None),
-            '+' => self.add_token(self.at-1, TokenType::PLUS, None),
-            ';' => self.add_token(self.at-1, TokenType::SEMICOLON, None),
-            '*' => self.add_token(self.at-1, TokenType::STAR, None),
+            '(' => self.add_token(TokenType::LEFT_PAREN, None),
+            ')' => self.add_token(TokenType::RIGHT_PAREN, None),
+            '{' => {
+                if let Some(open) = self.interpolations.last_mut() {
+                    *open += 1;
+                }
+                self.add_token(TokenType::LEFT_BRACE, None);
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string();
+                }
+                Some(open) => {
+                    *open -= 1;
+                    self.add_token(TokenType::RIGHT_BRACE, None);
+                }
+                None => self.add_token(TokenType::RIGHT_BRACE, None),
+            },
+            ',' => self.add_token(TokenType::COMMA, None),
+            '.' => self.add_token(TokenType::DOT, None),
+            '-' => self.add_token(TokenType::MINUS, None),
+            '+' => self.add_token(TokenType::PLUS, None),
+            ';' => self.add_token(TokenType::SEMICOLON, None),
+            '*' => self.add_token(TokenType::STAR, None),
 
             // Operators
             '!' => self.peek_and_add_token('=', TokenType::BANG_EQUAL, TokenType::BANG),
             '=' => self.peek_and_add_token('=', TokenType::EQUAL_EQUAL, TokenType::EQUAL),
             '<' => self.peek_and_add_token('=', TokenType::LESS_EQUAL, TokenType::LESS),
-            '>' => self.peek_and_add_token('=', TokenType::GREATER_EQUAL, TokenType::GREATER), 
-
-            // slash is a special case because of comments
-            '/' => {
-                if self.match_next_char('/') {
-                    // A comment goes until the end of the line.
-                    self.start += 2;
-                    loop {
-                        self.start += 1;
-                        self.at += 1;
-                        match self.next_char() {
-                            Some('\n') => {
-                                self.line += 1;
-                                break;
+            '>' => self.peek_and_add_token('=', TokenType::GREATER_EQUAL, TokenType::GREATER),
+
+            '/' => self.add_token(TokenType::SLASH, None),
+
+            // Strings
+            '"' => self.string(),
+
+            // Number Literals
+            // Number literals will always start with a digit
+            // -123 is not a literal but an expression
+            d if d.is_ascii_digit() => self.number(d),
+
+            c if c == '_' || is_xid_start(c) => self.identifier(),
+
+            _ => {
+                self.error("Unexpected character.");
+                self.add_error_token();
+            }
+        }
+    }
+
+    /// Scans the rest of an identifier or keyword. Identifiers follow the
+    /// Unicode XID rules, with `_` also allowed first, and their lexeme is
+    /// the NFC form of the source text so that names which look the same
+    /// are the same name.
+    fn identifier(&mut self) {
+        while self.peek_first_char().is_some_and(is_xid_continue) {
+            self.advance();
+        }
+
+        let text = &self.source[self.start..self.current];
+        let name = if is_nfc(text) { None } else { Some(text.nfc().collect::<String>()) };
+        let kind = match keywords::KEYWORDS.get(name.as_deref().unwrap_or(text)) {
+            Some(kind) => { *kind },
+            None => TokenType::IDENTIFIER,
+        };
+
+        let mut token = self.make_token(kind, None);
+        if let Some(name) = name {
+            let source_text = std::mem::replace(&mut token.lexeme, name);
+            token.extra.get_or_insert_with(Box::default).source_text = Some(source_text);
+        }
+        self.pending.push_back(Ok(token));
+    }
+
+    /// Consumes one piece of trivia if there is one at the current character:
+    /// a run of spaces, tabs and carriage returns, a newline, or a comment.
+    /// An unterminated block comment isn't trivia: it becomes an ERROR token
+    /// running to the end of the source, and this returns `None`.
+    fn scan_trivia(&mut self) -> Option<TriviaKind> {
+        let kind = match (self.peek_first_char()?, self.peek_second_char()) {
+            (' ' | '\r' | '\t', _) => {
+                while self.peek_first_char().is_some_and(|c| matches!(c, ' ' | '\r' | '\t')) {
+                    self.advance();
+                }
+                TriviaKind::Whitespace
+            }
+            ('\n', _) => {
+                self.advance();
+                TriviaKind::Newline
+            }
+            ('/', Some('/')) => {
+                // A comment goes until the end of the line.
+                while self.peek_first_char().is_some_and(|c| c != '\n') {
+                    self.advance();
+                }
+                TriviaKind::LineComment
+            }
+            ('/', Some('*')) => {
+                self.advance();
+                self.advance();
+                // A multiline comment goes until it finds a closing `*/`.
+                let mut depth = 1;
+                while depth > 0 {
+                    match self.next_char() {
+                        Some('*') => {
+                            if self.match_next_char('/') {
+                                depth -= 1;
                             }
-                            Some(_) => { }
-                            None => break,
                         }
-                    }
-                } else if self.match_next_char('*') {
-                    // A multiline comment goes until it finds a closing `*/`.
-                    self.start += 2;
-                    let mut depth = 1;
-                    while depth > 0 {
-                        self.start += 1;
-                        self.at += 1;
-                        match self.next_char() {
-                            Some('\n') => {
-                                self.line += 1;
-                            }
-                            Some('*') => { 
-                                if self.match_next_char('/') {
-                                    self.start += 1;
-                                    depth -= 1;
-                                }
-                            }
-                            Some('/') => {
-                                if self.match_next_char('*') {
-                                    self.start += 1;
-                                    depth += 1;
-                                }
+                        Some('/') => {
+                            if self.match_next_char('*') {
+                                depth += 1;
                             }
-                            Some(_) => { }
-                            None => errors::error(self.line, String::from("Unterminated multiline comment")),
+                        }
+                        Some(_) => { }
+                        None => {
+                            self.error_unterminated("/*", "Unterminated multiline comment.");
+                            self.add_error_token();
+                            return None;
                         }
                     }
-                } else {
-                    self.add_token(self.at-1, TokenType::SLASH, None);
                 }
+                TriviaKind::BlockComment
             }
-            ' ' | '\r' | '\t' => self.start += 1,
-            '\n' => {
-                self.line += 1;
-                self.start += 1;
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    /// The trivia just scanned, from the start of the current token on.
+    fn trivia(&self, kind: TriviaKind) -> Trivia {
+        Trivia {
+            kind,
+            text: self.source[self.start..self.current].to_string(),
+            span: Span::new(self.start, self.current),
+        }
+    }
+
+    /// Gives the token just queued, if any, the trivia after it on its line.
+    fn scan_trailing_trivia(&mut self) {
+        let Some(index) = self.pending.iter().rposition(Result::is_ok) else { return };
+
+        let mut trailing = Vec::new();
+        while !matches!(self.peek_first_char(), Some('\n') | None) {
+            self.begin_token();
+            match self.scan_trivia() {
+                Some(kind) => trailing.push(self.trivia(kind)),
+                None => break,
             }
+        }
 
-            // Strings
-            '"' => {
-                loop {
-                    match self.peek_first_char() {
-                        Some('"') => {
-                            let current_pos = self.at;
-                            self.advance();
-
-                            let value = &self.source[self.start+1..=current_pos-1];
-                            self.add_token(current_pos, TokenType::STRING, Some(value));
-                            break;
-                        }
-                        Some(c) => { 
-                            if c == '\n' { self.line += 1; }
-                            self.advance();
-                        }
-                        None => errors::error(self.line, String::from("Unterminated String")),
+        if let Some(Ok(Token { extra: Some(extra), .. })) = self.pending.get_mut(index) {
+            extra.trailing = trailing;
+        }
+    }
+
+    /// Scans the rest of a string literal, processing escapes into its value.
+    /// Strings may span lines; a `\r\n` inside one becomes `\n`. A `${` ends
+    /// the fragment as an INTERPOLATION, and the string carries on after the
+    /// `}` that closes the embedded expression.
+    fn string(&mut self) {
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => break,
+                Some('$') if self.match_next_char('{') => {
+                    self.interpolations.push(0);
+                    self.add_token(TokenType::INTERPOLATION, Some(LiteralValue::Str(value)));
+                    return;
+                }
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                Some('\r') if self.peek_first_char() == Some('\n') => {}
+                Some(c) => value.push(c),
+                None => {
+                    self.error_unterminated("\"", "Unterminated string.");
+                    self.add_error_token();
+                    return;
+                }
+            }
+        }
+
+        self.add_token(TokenType::STRING, Some(LiteralValue::Str(value)));
+    }
+
+    /// Scans an escape sequence after its backslash. Invalid escapes are
+    /// reported with a span covering the whole sequence and give `None`.
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        let (line, column) = (self.line, self.column - 1);
+
+        let c = match self.peek_first_char() {
+            // Leave reporting the unterminated string to the caller.
+            None => return None,
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('$') => '$',
+            Some('u') => {
+                self.advance();
+                return self.unicode_escape(start, line, column);
+            }
+            Some(_) => {
+                self.advance();
+                let message = format!("Unknown escape sequence '{}'.", &self.source[start..self.current]);
+                self.error_from(start, line, column, &message);
+                return None;
+            }
+        };
+
+        self.advance();
+        Some(c)
+    }
+
+    /// Scans the `{XXXX}` of a `\u{XXXX}` escape: one to six hex digits naming
+    /// a Unicode scalar value.
+    fn unicode_escape(&mut self, start: usize, line: usize, column: usize) -> Option<char> {
+        let braced = self.match_next_char('{');
+        let digits_start = self.current;
+        while self.peek_first_char().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+
+        if !braced || digits.is_empty() || digits.len() > 6 || !self.match_next_char('}') {
+            self.error_from(start, line, column, "Expected 1 to 6 hex digits in braces after '\\u', like '\\u{1F600}'.");
+            return None;
+        }
+
+        let c = u32::from_str_radix(digits, 16).ok().and_then(char::from_u32);
+        if c.is_none() {
+            let message = format!("'{}' is not a valid Unicode character.", &self.source[start..self.current]);
+            self.error_from(start, line, column, &message);
+        }
+        c
+    }
+
+    /// Scans the rest of a number literal whose first digit is `first`: a
+    /// decimal with an optional fraction and exponent, or a `0x`, `0o` or `0b`
+    /// integer. Any of them may use `_` between digits.
+    fn number(&mut self, first: char) {
+        // Anything queued while scanning the literal is an error about it.
+        let queued = self.pending.len();
+        let radix = match (first, self.peek_first_char()) {
+            ('0', Some('x')) => Some((16, "hex")),
+            ('0', Some('o')) => Some((8, "octal")),
+            ('0', Some('b')) => Some((2, "binary")),
+            _ => None,
+        };
+
+        let value = match radix {
+            Some((radix, name)) => {
+                self.advance();
+                let digits = self.digits(radix, String::new());
+
+                // A letter or digit straight after the literal would otherwise
+                // start a new token, so `0b102` is one bad literal, not `0b10 2`.
+                if self.peek_first_char().is_some_and(|c| c.is_alphanumeric()) {
+                    let bad_start = self.current;
+                    let bad = self.advance();
+                    while self.peek_first_char().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                        self.advance();
                     }
+                    self.error_since(bad_start, &format!("Invalid digit '{}' in {} literal.", bad, name));
                 }
+
+                if digits.is_empty() && self.pending.len() == queued {
+                    let prefix = &self.source[self.start..self.start + 2];
+                    self.error(&format!("Expected {} digits after '{}'.", name, prefix));
+                }
+
+                digits.chars()
+                    .filter_map(|digit| digit.to_digit(radix))
+                    .fold(0.0, |value, digit| value * radix as f64 + digit as f64)
             }
+            None => {
+                let mut literal = self.digits(10, first.to_string());
 
-            // Number Literals
-            // Number literals will always start with a digit
-            // -123 is not a literal but an expression
-            d if d.is_ascii_digit() => {
-                loop {
-                   match self.peek_first_char() {
-                        Some(d) if d.is_ascii_digit() => { self.advance(); }
-                        Some('.') => {
-                            let after_point = self.peek_second_char();
-                            if let Some(d) = after_point {
-                                if d.is_ascii_digit() {
-                                    self.advance();
-                                }
-                            }
-                        }
-                        Some(_) => {
-                            let pos = self.at - 1;
-                            let num_str = &self.source[self.start..=pos];
-                            self.add_token(pos ,TokenType::NUMBER, Some(num_str));
-                            break;
-                        }
-                        _ => break,
-                    } 
-                }
-            }
-
-            c if c.is_alphanumeric() => {
-                loop {
-                    match self.peek_first_char() {
-                        Some(c) if c.is_alphanumeric() => { self.advance(); }
-                        Some(_) => {
-                            let pos = self.at - 1;
-                            let text = &self.source[self.start..=pos];
-                            let kind = match keywords::KEYWORDS.get(text) {
-                                Some(kind) => { *kind },
-                                None => TokenType::IDENTIFIER,
-                            };
-                            self.add_token(pos, kind, None);
-                            break
-                        }
-                        _ => break,
+                if self.peek_first_char() == Some('.') && self.peek_second_char().is_some_and(|c| c.is_ascii_digit()) {
+                    self.advance();
+                    literal.push('.');
+                    literal = self.digits(10, literal);
+                }
+
+                if let Some(e @ ('e' | 'E')) = self.peek_first_char() {
+                    let exponent_start = self.current;
+                    self.advance();
+                    literal.push(e);
+                    if let Some(sign @ ('+' | '-')) = self.peek_first_char() {
+                        self.advance();
+                        literal.push(sign);
+                    }
+
+                    if self.peek_first_char().is_some_and(|c| c.is_ascii_digit()) {
+                        literal = self.digits(10, literal);
+                    } else {
+                        self.error_since(exponent_start, "Expected digits after the exponent.");
+                        literal.push('0');
                     }
                 }
+
+                literal.parse().expect("scanned number literal is a valid f64")
             }
+        };
 
-            _ => {
-                errors::error(self.line, "Unexpected character.".to_string());
-                self.failed = true;
-                self.start += 1;
+        self.add_token(TokenType::NUMBER, Some(LiteralValue::Number(value)));
+    }
+
+    /// Consumes a run of digits in `radix` and appends them to `digits`
+    /// without their `_` separators. A separator must sit between two digits.
+    fn digits(&mut self, radix: u32, mut digits: String) -> String {
+        while let Some(c) = self.peek_first_char() {
+            if c.is_digit(radix) {
+                digits.push(c);
+                self.advance();
+            } else if c == '_' {
+                let separator_start = self.current;
+                while self.peek_first_char() == Some('_') {
+                    self.advance();
+                }
+
+                let between_digits = digits.ends_with(|c: char| c.is_digit(radix))
+                    && self.current - separator_start == 1
+                    && self.peek_first_char().is_some_and(|c| c.is_digit(radix));
+                if !between_digits {
+                    self.error_since(separator_start, "A digit separator '_' must sit between two digits.");
+                }
+            } else {
+                break;
             }
         }
+
+        digits
+    }
+
+    /// Reports an error pointing at the text of the token being scanned.
+    fn error(&mut self, message: &str) {
+        self.error_from(self.start, self.start_line, self.start_column, message);
+    }
+
+    /// Reports an error pointing from `start`, on the current line, up to the
+    /// current character.
+    fn error_since(&mut self, start: usize, message: &str) {
+        let column = self.column - self.source[start..self.current].chars().count();
+        self.error_from(start, self.line, column, message);
+    }
+
+    /// Reports an error pointing from `start`, at `line` and `column`, up to
+    /// the current character.
+    fn error_from(&mut self, start: usize, line: usize, column: usize, message: &str) {
+        let location = Location {
+            span: Span::new(start, self.current),
+            line,
+            column,
+            text: self.source[start..self.current].to_string(),
+        };
+        self.report(ScanError::new(location, message));
     }
 
-    fn add_token(&mut self, at: usize, kind: TokenType, literal: Option<&'a str>) {
-        let text = &self.source[self.start..=at];
-        let token = Token::new(kind, text, literal, self.line);
+    /// Reports an error pointing just past the last character of the source.
+    fn error_at_end(&mut self, message: &str) {
+        let location = self.end_of_input();
+        self.report(ScanError::new(location, message));
+    }
 
-        self.start = at+1;
-        self.tokens.push(token);
+    /// Reports a token that ran to the end of the input, pointing at the
+    /// `opening` delimiter it starts with.
+    fn error_unterminated(&mut self, opening: &str, message: &str) {
+        let location = Location {
+            span: Span::new(self.start, self.start + opening.len()),
+            line: self.start_line,
+            column: self.start_column,
+            text: opening.to_string(),
+        };
+        let error = ScanError::new(location, message).with_label(self.end_of_input(), "the input ends here");
+        self.report(error);
+    }
+
+    /// Queues `error` ahead of the token it is about, which becomes an ERROR token.
+    fn report(&mut self, error: ScanError) {
+        self.token_error.get_or_insert_with(|| error.message.clone());
+        self.pending.push_back(Err(error));
+    }
+
+    /// Where the source ends. Only valid once all of it has been consumed.
+    fn end_of_input(&self) -> Location {
+        let end = self.source.len();
+        Location { span: Span::new(end, end), line: self.line, column: self.column, text: String::new() }
+    }
+
+    fn add_token(&mut self, kind: TokenType, literal: Option<LiteralValue>) {
+        let token = self.make_token(kind, literal);
+        self.pending.push_back(Ok(token));
+    }
+
+    /// Covers text that isn't any token, which has already been reported,
+    /// with an ERROR token so the token stream still spans the whole source.
+    fn add_error_token(&mut self) {
+        self.add_token(TokenType::ERROR, None);
+    }
+
+    /// A token for the text scanned since `begin_token`, taking any leading
+    /// trivia. Text with an error reported in it, such as a string with a bad
+    /// escape, becomes an ERROR token whatever it was going to be.
+    fn make_token(&mut self, kind: TokenType, literal: Option<LiteralValue>) -> Token {
+        let error = self.token_error.take();
+        let (kind, literal) = if error.is_some() { (TokenType::ERROR, None) } else { (kind, literal) };
+
+        let text = &self.source[self.start..self.current];
+        let mut token = Token::new(kind, text, literal, self.start_line)
+            .with_position(Span::new(self.start, self.current), self.start_column);
+        if self.keep_trivia || error.is_some() {
+            let leading = std::mem::take(&mut self.leading_trivia);
+            token.extra = Some(Box::new(TokenExtra { leading, error, ..TokenExtra::default() }));
+        }
+
+        token
     }
 
     fn advance(&mut self) -> char {
-        self.at += 1;
-        self.next_char().expect("called scan_token with no remaining chars")
+        self.next_char().expect("called advance with no remaining chars")
     }
 
+    /// Consumes the next character, keeping the line bookkeeping up to date.
     fn next_char(&mut self) -> Option<char> {
-        self.current.next()
+        let c = self.peek_first_char()?;
+        self.current += c.len_utf8();
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
     }
 
     fn peek_first_char(&self) -> Option<char> {
-        self.current.clone().next()
+        self.source[self.current..].chars().next()
     }
 
     fn peek_second_char(&self) -> Option<char> {
-        let mut iter_clone = self.current.clone();
-        iter_clone.next();
-        iter_clone.next()
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next()
     }
 
     fn match_next_char(&mut self, expected: char) -> bool {
@@ -236,6 +588,438 @@ impl<'a> Scanner<'a> {
             }
             _ => false,
         }
-        
+    }
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            self.begin_token();
+            if let Some(kind) = self.scan_trivia() {
+                if self.keep_trivia {
+                    let trivia = self.trivia(kind);
+                    self.leading_trivia.push(trivia);
+                }
+            } else if self.is_at_end() {
+                // An unterminated block comment may have run to the end since `begin_token`.
+                self.begin_token();
+                if !self.interpolations.is_empty() {
+                    self.error_at_end("Unterminated string interpolation.");
+                    self.add_error_token();
+                }
+                self.add_token(TokenType::EOF, None);
+                self.finished = true;
+            } else {
+                self.scan_token();
+                if self.keep_trivia {
+                    self.scan_trailing_trivia();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scan_errors(source: &str) -> Vec<String> {
+        match Scanner::new(source).scan_tokens() {
+            Ok(tokens) => panic!("expected {:?} to fail scanning, got {:?}", source, tokens),
+            Err(errors) => errors.iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn reports_every_unexpected_character() {
+        assert_eq!(scan_errors("@\nvar a = #;"), [
+            "[line 1] Error: Unexpected character.",
+            "[line 2] Error: Unexpected character.",
+        ]);
+    }
+
+    #[test]
+    fn unterminated_literals_are_errors() {
+        assert_eq!(scan_errors("\"abc"), ["[line 1] Error: Unterminated string."]);
+        assert_eq!(scan_errors("/* abc\n"), ["[line 1] Error: Unterminated multiline comment."]);
+    }
+
+    fn positions(source: &str) -> Vec<(String, usize, usize, Span)> {
+        Scanner::new(source).scan_tokens().expect("scanning failed").iter()
+            .map(|token| (token.lexeme.clone(), token.line, token.column, token.span))
+            .collect()
+    }
+
+    #[test]
+    fn records_spans_lines_and_columns() {
+        assert_eq!(positions("var ab = 1;\n  ab >= 2;"), [
+            ("var".to_string(), 1, 1, Span::new(0, 3)),
+            ("ab".to_string(), 1, 5, Span::new(4, 6)),
+            ("=".to_string(), 1, 8, Span::new(7, 8)),
+            ("1".to_string(), 1, 10, Span::new(9, 10)),
+            (";".to_string(), 1, 11, Span::new(10, 11)),
+            ("ab".to_string(), 2, 3, Span::new(14, 16)),
+            (">=".to_string(), 2, 6, Span::new(17, 19)),
+            ("2".to_string(), 2, 9, Span::new(20, 21)),
+            (";".to_string(), 2, 10, Span::new(21, 22)),
+            ("".to_string(), 2, 11, Span::new(22, 22)),
+        ]);
+    }
+
+    #[test]
+    fn handles_non_ascii_source() {
+        let tokens = positions("print \"héllo wörld\"; // ünïcode\nx");
+
+        assert_eq!(tokens[1], ("\"héllo wörld\"".to_string(), 1, 7, Span::new(6, 21)));
+        assert_eq!(tokens[2], (";".to_string(), 1, 20, Span::new(21, 22)));
+        assert_eq!(tokens[3], ("x".to_string(), 2, 1, Span::new(36, 37)));
+    }
+
+    #[test]
+    fn multiline_strings_start_where_they_open() {
+        let tokens = positions("  \"a\nb\" c");
+
+        assert_eq!(tokens[0], ("\"a\nb\"".to_string(), 1, 3, Span::new(2, 7)));
+        assert_eq!(tokens[1], ("c".to_string(), 2, 4, Span::new(8, 9)));
+    }
+
+    fn string_value(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        match tokens[0].literal {
+            Some(LiteralValue::Str(ref value)) => value.clone(),
+            ref other => panic!("expected a string literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn processes_escape_sequences() {
+        assert_eq!(string_value(r#""a\nb\tc\rd\\e\"f\0""#), "a\nb\tc\rd\\e\"f\0");
+        assert_eq!(string_value(r#""\u{41}\u{e9}\u{1F600}\u{000000}""#), "Aé😀\0");
+        assert_eq!(string_value("\"one\r\ntwo\nthree\""), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn the_lexeme_keeps_the_escapes() {
+        let tokens = positions(r#"x = "\"hi\"";"#);
+
+        assert_eq!(tokens[2], (r#""\"hi\"""#.to_string(), 1, 5, Span::new(4, 12)));
+        assert_eq!(tokens[3], (";".to_string(), 1, 13, Span::new(12, 13)));
+    }
+
+    #[test]
+    fn invalid_escapes_are_reported_with_their_span() {
+        let source = "var s = \"ok\nfine \\q \\u{D800} \\u{1234567} \\u41\";";
+        let errors = Scanner::new(source).scan_tokens().unwrap_err();
+        let reported: Vec<_> = errors.iter()
+            .map(|error| (error.message.as_str(), error.location.text.as_str(), error.location.line, error.location.column))
+            .collect();
+
+        assert_eq!(reported, [
+            ("Unknown escape sequence '\\q'.", "\\q", 2, 6),
+            ("'\\u{D800}' is not a valid Unicode character.", "\\u{D800}", 2, 9),
+            ("Expected 1 to 6 hex digits in braces after '\\u', like '\\u{1F600}'.", "\\u{1234567", 2, 18),
+            ("Expected 1 to 6 hex digits in braces after '\\u', like '\\u{1F600}'.", "\\u41", 2, 30),
+        ]);
+        assert_eq!(errors[0].location.span, Span::new(17, 19));
+    }
+
+    #[test]
+    fn an_escaped_quote_does_not_end_the_string() {
+        assert_eq!(scan_errors(r#""abc\""#), ["[line 1] Error: Unterminated string."]);
+        assert_eq!(scan_errors("\"abc\\"), ["[line 1] Error: Unterminated string."]);
+    }
+
+    fn kinds_and_lexemes(source: &str) -> Vec<(TokenType, String)> {
+        Scanner::new(source).scan_tokens().expect("scanning failed").iter()
+            .map(|token| (token.kind, token.lexeme.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_interpolated_strings_into_fragments() {
+        use TokenType::*;
+
+        let tokens = kinds_and_lexemes(r#""a ${x} b ${ "c${y}" + f({}) } d\${z}""#);
+        let expected = [
+            (INTERPOLATION, r#""a ${"#), (IDENTIFIER, "x"), (INTERPOLATION, "} b ${"),
+            (INTERPOLATION, r#""c${"#), (IDENTIFIER, "y"), (STRING, r#"}""#),
+            (PLUS, "+"), (IDENTIFIER, "f"), (LEFT_PAREN, "("), (LEFT_BRACE, "{"), (RIGHT_BRACE, "}"), (RIGHT_PAREN, ")"),
+            (STRING, r#"} d\${z}""#), (EOF, ""),
+        ];
+        assert_eq!(tokens, expected.map(|(kind, lexeme)| (kind, lexeme.to_string())));
+
+        assert_eq!(string_value(r#""} d\${z}""#), "} d${z}");
+    }
+
+    #[test]
+    fn fragments_carry_their_text_and_position() {
+        let mut scanner = Scanner::new("\"Hi ${\nname}!\"");
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+
+        assert_eq!(tokens[0].literal, Some(LiteralValue::Str(String::from("Hi "))));
+        assert_eq!(tokens[2].literal, Some(LiteralValue::Str(String::from("!"))));
+        assert_eq!((tokens[2].line, tokens[2].column, tokens[2].span), (2, 5, Span::new(11, 14)));
+    }
+
+    #[test]
+    fn unterminated_interpolations_are_errors() {
+        assert_eq!(scan_errors("\"a ${b"), ["[line 1] Error: Unterminated string interpolation."]);
+        assert_eq!(scan_errors("\"a ${b} c"), ["[line 1] Error: Unterminated string."]);
+    }
+
+    fn number(source: &str) -> f64 {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        assert_eq!(tokens.len(), 2, "expected {:?} to be one token", source);
+        tokens[0].number().expect("expected a number")
+    }
+
+    #[test]
+    fn scans_number_literals() {
+        assert_eq!(number("123"), 123.0);
+        assert_eq!(number("123.45"), 123.45);
+        assert_eq!(number("0xFF"), 255.0);
+        assert_eq!(number("0xdead_BEEF"), 3735928559.0);
+        assert_eq!(number("0b1010"), 10.0);
+        assert_eq!(number("0o755"), 493.0);
+        assert_eq!(number("1_000_000"), 1_000_000.0);
+        assert_eq!(number("6.02e23"), 6.02e23);
+        assert_eq!(number("1e-9"), 1e-9);
+        assert_eq!(number("2E+3"), 2000.0);
+        assert_eq!(number("1_0.2_5e1_0"), 10.25e10);
+    }
+
+    #[test]
+    fn numbers_keep_their_lexeme() {
+        assert_eq!(kinds_and_lexemes("0x1_F.a 1.2.3"), [
+            (TokenType::NUMBER, String::from("0x1_F")),
+            (TokenType::DOT, String::from(".")),
+            (TokenType::IDENTIFIER, String::from("a")),
+            (TokenType::NUMBER, String::from("1.2")),
+            (TokenType::DOT, String::from(".")),
+            (TokenType::NUMBER, String::from("3")),
+            (TokenType::EOF, String::new()),
+        ]);
+    }
+
+    #[test]
+    fn reports_malformed_numbers_precisely() {
+        let errors = |source: &str| -> Vec<(String, String)> {
+            Scanner::new(source).scan_tokens().unwrap_err().iter()
+                .map(|error| (error.message.clone(), error.location.text.clone()))
+                .collect()
+        };
+        let error = |message: &str, text: &str| vec![(message.to_string(), text.to_string())];
+        let separator = "A digit separator '_' must sit between two digits.";
+
+        assert_eq!(errors("0x;"), error("Expected hex digits after '0x'.", "0x"));
+        assert_eq!(errors("0b"), error("Expected binary digits after '0b'.", "0b"));
+        assert_eq!(errors("0b102"), error("Invalid digit '2' in binary literal.", "2"));
+        assert_eq!(errors("0o78_9"), error("Invalid digit '8' in octal literal.", "8_9"));
+        assert_eq!(errors("0xFG"), error("Invalid digit 'G' in hex literal.", "G"));
+        assert_eq!(errors("1__0"), error(separator, "__"));
+        assert_eq!(errors("1_ + 2"), error(separator, "_"));
+        assert_eq!(errors("1_.5"), error(separator, "_"));
+        assert_eq!(errors("0x_1"), error(separator, "_"));
+        assert_eq!(errors("1e"), error("Expected digits after the exponent.", "e"));
+        assert_eq!(errors("1.5e+x"), error("Expected digits after the exponent.", "e+"));
+
+        let located = Scanner::new("var a =\n  1__0;").scan_tokens().unwrap_err();
+        assert_eq!((located[0].location.line, located[0].location.column, located[0].location.span), (2, 4, Span::new(11, 13)));
+    }
+
+    #[test]
+    fn yields_tokens_and_errors_in_source_order() {
+        let mut scanner = Scanner::new("1 @ // note\n 2");
+        let items: Vec<String> = scanner.by_ref()
+            .map(|item| match item {
+                Ok(token) => format!("{:?} {}", token.kind, token.lexeme),
+                Err(error) => error.to_string(),
+            })
+            .collect();
+
+        assert_eq!(items, ["NUMBER 1", "[line 1] Error: Unexpected character.", "ERROR @", "NUMBER 2", "EOF "]);
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn scans_only_as_far_as_asked() {
+        let source = format!("var a = 1;{}", " @".repeat(1000));
+        let first: Vec<_> = Scanner::new(&source).take(4).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(first.last().map(|token| token.kind), Some(TokenType::NUMBER));
+        assert_eq!(Scanner::new("").map(|item| item.unwrap().kind).collect::<Vec<_>>(), [TokenType::EOF]);
+    }
+
+    fn trivia_texts(trivia: &[Trivia]) -> Vec<(TriviaKind, &str)> {
+        trivia.iter().map(|trivia| (trivia.kind, trivia.text.as_str())).collect()
+    }
+
+    #[test]
+    fn attaches_trivia_to_the_tokens_around_it() {
+        use TriviaKind::*;
+
+        let source = "// header\nvar a = 1; /* one */ // two\n\n  /* a\n b */ print a;\r\n";
+        let tokens = Scanner::new(source).with_trivia().scan_tokens().expect("scanning failed");
+
+        assert_eq!(trivia_texts(tokens[0].leading_trivia()), [(LineComment, "// header"), (Newline, "\n")]);
+        assert_eq!(trivia_texts(tokens[0].trailing_trivia()), [(Whitespace, " ")]);
+        assert_eq!(trivia_texts(tokens[4].trailing_trivia()), [
+            (Whitespace, " "), (BlockComment, "/* one */"), (Whitespace, " "), (LineComment, "// two"),
+        ]);
+        assert_eq!(trivia_texts(tokens[5].leading_trivia()), [
+            (Newline, "\n"), (Newline, "\n"), (Whitespace, "  "), (BlockComment, "/* a\n b */"), (Whitespace, " "),
+        ]);
+        assert_eq!(trivia_texts(tokens[7].trailing_trivia()), [(Whitespace, "\r")]);
+        assert_eq!(trivia_texts(tokens[8].leading_trivia()), [(Newline, "\n")]);
+        assert_eq!(tokens[5].leading_trivia()[3].span, Span::new(41, 51));
+
+        assert_eq!(tokens.iter().map(Token::full_text).collect::<String>(), source);
+    }
+
+    #[test]
+    fn skips_trivia_by_default() {
+        let tokens = Scanner::new("  a // b\n").scan_tokens().expect("scanning failed");
+
+        assert!(tokens.iter().all(|token| token.leading_trivia().is_empty() && token.trailing_trivia().is_empty()));
+        assert_eq!(tokens.iter().map(Token::full_text).collect::<String>(), "a");
+    }
+
+    #[test]
+    fn identifiers_may_use_underscores() {
+        let tokens = kinds_and_lexemes("my_var _private __init__ _ x1 1x");
+        let identifiers: Vec<_> = tokens.iter().map(|(kind, lexeme)| format!("{:?} {}", kind, lexeme)).collect();
+
+        assert_eq!(identifiers, [
+            "IDENTIFIER my_var", "IDENTIFIER _private", "IDENTIFIER __init__", "IDENTIFIER _", "IDENTIFIER x1",
+            "NUMBER 1", "IDENTIFIER x", "EOF ",
+        ]);
+    }
+
+    #[test]
+    fn identifiers_follow_unicode_xid_rules() {
+        let tokens = kinds_and_lexemes("π 変数 переменная ñandú_2 ab\u{0301}c");
+        let lexemes: Vec<_> = tokens.iter().map(|(_, lexeme)| lexeme.as_str()).collect();
+
+        assert!(tokens[..5].iter().all(|(kind, _)| *kind == TokenType::IDENTIFIER));
+        assert_eq!(lexemes, ["π", "変数", "переменная", "ñandú_2", "ab\u{0301}c", ""]);
+
+        // Symbols and emoji aren't XID characters, and neither is a lone combining mark.
+        assert_eq!(scan_errors("€ 😀 \u{0301}x"), [
+            "[line 1] Error: Unexpected character.",
+            "[line 1] Error: Unexpected character.",
+            "[line 1] Error: Unexpected character.",
+        ]);
+    }
+
+    #[test]
+    fn identifiers_are_normalized_to_nfc() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let source = format!("{} {}", composed, decomposed);
+        let tokens = Scanner::new(&source).with_trivia().scan_tokens().expect("scanning failed");
+
+        assert_eq!(tokens[0].lexeme, composed);
+        assert_eq!(tokens[1].lexeme, composed);
+        assert_eq!(tokens[1].span, Span::new(6, 12));
+        assert_eq!(tokens.iter().map(Token::full_text).collect::<String>(), source);
+
+        let tokens = Scanner::new(decomposed).scan_tokens().expect("scanning failed");
+        assert_eq!(tokens[0].lexeme, composed);
+        assert_eq!(tokens[0].source_text(), decomposed);
+    }
+
+    #[test]
+    fn error_tokens_cover_text_that_is_not_a_token() {
+        let kinds = |source: &str| -> Vec<(TokenType, String, Span)> {
+            Scanner::new(source)
+                .filter_map(Result::ok)
+                .map(|token| (token.kind, token.lexeme, token.span))
+                .collect()
+        };
+
+        assert_eq!(kinds("a @é b"), [
+            (TokenType::IDENTIFIER, String::from("a"), Span::new(0, 1)),
+            (TokenType::ERROR, String::from("@"), Span::new(2, 3)),
+            (TokenType::IDENTIFIER, String::from("é"), Span::new(3, 5)),
+            (TokenType::IDENTIFIER, String::from("b"), Span::new(6, 7)),
+            (TokenType::EOF, String::new(), Span::new(7, 7)),
+        ]);
+        assert_eq!(kinds("x \"ab\nc")[1], (TokenType::ERROR, String::from("\"ab\nc"), Span::new(2, 7)));
+        assert_eq!(kinds("x /* a /* b */")[1], (TokenType::ERROR, String::from("/* a /* b */"), Span::new(2, 14)));
+
+        let tokens: Vec<Token> = Scanner::new("#").filter_map(Result::ok).collect();
+        assert_eq!(tokens[0].literal, None);
+        assert_eq!(tokens[0].error(), Some("Unexpected character."));
+    }
+
+    #[test]
+    fn every_scan_error_leaves_an_error_token() {
+        let errors = |source: &str| -> Vec<(TokenType, String, Option<String>)> {
+            Scanner::new(source)
+                .filter_map(Result::ok)
+                .filter(|token| token.kind == TokenType::ERROR)
+                .map(|token| (token.kind, token.lexeme.clone(), token.error().map(String::from)))
+                .collect()
+        };
+        let error = |lexeme: &str, message: &str| (TokenType::ERROR, String::from(lexeme), Some(String::from(message)));
+
+        assert_eq!(errors(r#"print "a\q\u{D800}";"#), [error(r#""a\q\u{D800}""#, "Unknown escape sequence '\\q'.")]);
+        assert_eq!(errors("print 0b12 + 1__0;"), [
+            error("0b12", "Invalid digit '2' in binary literal."),
+            error("1__0", "A digit separator '_' must sit between two digits."),
+        ]);
+        assert_eq!(errors("\"a ${b"), [error("", "Unterminated string interpolation.")]);
+        assert_eq!(errors("\"a ${\"\\q\"} b\""), [error("\"\\q\"", "Unknown escape sequence '\\q'.")]);
+    }
+
+    #[test]
+    fn keeps_scanning_after_errors() {
+        let items: Vec<_> = Scanner::new("@ 1 # \"open").collect();
+        let errors = items.iter().filter(|item| item.is_err()).count();
+        let kinds: Vec<_> = items.iter().filter_map(|item| item.as_ref().ok()).map(|token| token.kind).collect();
+
+        assert_eq!(errors, 3);
+        assert_eq!(kinds, [TokenType::ERROR, TokenType::NUMBER, TokenType::ERROR, TokenType::ERROR, TokenType::EOF]);
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_character() {
+        let errors = Scanner::new("a é@").scan_tokens().unwrap_err();
+
+        assert_eq!(errors[0].location.span, Span::new(4, 5));
+        assert_eq!(errors[0].location.column, 4);
+        assert_eq!(errors[0].location.text, "@");
+    }
+
+    #[test]
+    fn unterminated_tokens_are_reported_where_they_start() {
+        let errors = Scanner::new("print 1;
+print \"ab\ncd").scan_tokens().unwrap_err();
+        assert_eq!(errors[0].location, Location { span: Span::new(15, 16), line: 2, column: 7, text: String::from("\"") });
+        assert_eq!(errors[0].labels[0].location, Location { span: Span::new(21, 21), line: 3, column: 3, text: String::new() });
+        assert_eq!(errors[0].labels[0].message, "the input ends here");
+
+        let errors = Scanner::new("a /* b\n").scan_tokens().unwrap_err();
+        assert_eq!(errors[0].location, Location { span: Span::new(2, 4), line: 1, column: 3, text: String::from("/*") });
+        assert_eq!(errors[0].labels[0].location.line, 2);
+    }
+
+    #[test]
+    fn columns_count_characters_on_very_long_lines() {
+        let source = format!("{} @", "é".repeat(500_000));
+        let errors = Scanner::new(&source).scan_tokens().unwrap_err();
+        assert_eq!(errors[0].location.column, 500_002);
     }
 }
@@ -0,0 +1,99 @@
+/// A half-open range of byte offsets into the source, `start_byte..end_byte`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl Span {
+    pub fn new(start_byte: usize, end_byte: usize) -> Self {
+        Span { start_byte, end_byte }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end_byte - self.start_byte
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start_byte == self.end_byte
+    }
+}
+
+/// Maps byte offsets in a source back to 1-based lines and columns. Columns
+/// count characters rather than bytes so they match what an editor shows.
+#[derive(Debug)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    /// Byte offset at which every line starts; the first entry is always 0.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+
+        LineIndex { source, line_starts }
+    }
+
+    /// The line and column of the character at `offset`. An offset past the end
+    /// of the source is clamped to the end.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.line_starts[line]..offset].chars().count() + 1;
+
+        (line + 1, column)
+    }
+
+    /// The text of the 1-based `line`, without its line terminator.
+    pub fn line_text(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map_or(self.source.len(), |next| next - 1);
+
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn maps_offsets_to_lines_and_columns() {
+        let index = LineIndex::new("ab\ncd\n\nef");
+
+        assert_eq!(index.line_column(0), (1, 1));
+        assert_eq!(index.line_column(2), (1, 3));
+        assert_eq!(index.line_column(3), (2, 1));
+        assert_eq!(index.line_column(7), (4, 1));
+        assert_eq!(index.line_column(100), (4, 3));
+        assert_eq!(index.line_count(), 4);
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let source = "\"héllo\" + x";
+        let index = LineIndex::new(source);
+        let plus = source.find('+').unwrap();
+
+        assert_eq!(plus, 9);
+        assert_eq!(index.line_column(plus), (1, 9));
+    }
+
+    #[test]
+    fn returns_the_text_of_a_line() {
+        let index = LineIndex::new("one\r\ntwo\n");
+
+        assert_eq!(index.line_text(1), Some("one"));
+        assert_eq!(index.line_text(2), Some("two"));
+        assert_eq!(index.line_text(3), Some(""));
+        assert_eq!(index.line_text(0), None);
+        assert_eq!(index.line_text(4), None);
+    }
+}
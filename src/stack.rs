@@ -0,0 +1,11 @@
+/// Stack that must be left for the work between two calls to `grow`.
+const RED_ZONE: usize = 128 * 1024;
+/// Size of each new stack segment.
+const SEGMENT_SIZE: usize = 1024 * 1024;
+
+/// Runs `f`, first moving to a fresh stack segment if the current one is
+/// nearly used up. The recursive passes call this at every level, so deep
+/// programs don't depend on the size of the thread running them.
+pub(crate) fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
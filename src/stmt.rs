@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use super::Token;
+use super::expr::{Expr, ExprVisitor};
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Expression(Box<Expr>),
+    Print(Box<Expr>),
+    Var(Token, Option<Box<Expr>>),
+    Block(Vec<Stmt>),
+    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+    While(Box<Expr>, Box<Stmt>),
+    Function(Rc<FunctionDecl>),
+    Return(Token, Option<Box<Expr>>),
+    Class(Token, Option<Box<Expr>>, Vec<Rc<FunctionDecl>>),
+}
+
+/// Shared between the syntax tree and every function value created from it.
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+pub trait StmtVisitor<T> {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> T;
+}
+
+/// Prints statements as S-expressions, one per line at the top level, with
+/// every expression inside printed by `P`.
+pub struct StmtPrinter<P>(pub P);
+
+impl<P: ExprVisitor<String>> StmtPrinter<P> {
+    fn function(&mut self, keyword: &str, declaration: &FunctionDecl) -> String {
+        let params: Vec<&str> = declaration.params.iter().map(|param| param.lexeme.as_str()).collect();
+        let mut parts = vec![keyword.to_string(), declaration.name.lexeme.clone(), format!("({})", params.join(" "))];
+        parts.extend(declaration.body.iter().map(|stmt| self.visit_stmt(stmt)));
+        format!("({})", parts.join(" "))
+    }
+}
+
+impl<P: ExprVisitor<String>> StmtVisitor<String> for StmtPrinter<P> {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Expression(ref expr) => format!("(; {})", self.0.visit(expr)),
+            Stmt::Print(ref expr) => format!("(print {})", self.0.visit(expr)),
+            Stmt::Var(ref name, Some(ref initializer)) => {
+                format!("(var {} {})", name.lexeme, self.0.visit(initializer))
+            }
+            Stmt::Var(ref name, None) => format!("(var {})", name.lexeme),
+            Stmt::Block(ref statements) => {
+                let mut parts = vec![String::from("block")];
+                parts.extend(statements.iter().map(|stmt| self.visit_stmt(stmt)));
+                format!("({})", parts.join(" "))
+            }
+            Stmt::If(ref condition, ref then_branch, ref else_branch) => {
+                let mut parts = vec![String::from("if"), self.0.visit(condition), self.visit_stmt(then_branch)];
+                if let Some(else_branch) = else_branch {
+                    parts.push(self.visit_stmt(else_branch));
+                }
+                format!("({})", parts.join(" "))
+            }
+            Stmt::While(ref condition, ref body) => {
+                format!("(while {} {})", self.0.visit(condition), self.visit_stmt(body))
+            }
+            Stmt::Function(ref declaration) => self.function("fun", declaration),
+            Stmt::Return(_, Some(ref value)) => format!("(return {})", self.0.visit(value)),
+            Stmt::Return(_, None) => String::from("(return)"),
+            Stmt::Class(ref name, ref superclass, ref methods) => {
+                let mut parts = vec![String::from("class"), name.lexeme.clone()];
+                if let Some(superclass) = superclass {
+                    parts.push(String::from("<"));
+                    parts.push(self.0.visit(superclass));
+                }
+                parts.extend(methods.iter().map(|method| self.function("method", method)));
+                format!("({})", parts.join(" "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::{ASTPrinter, RPNPrinter};
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn print_with<P: ExprVisitor<String>>(printer: P, source: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens().expect("scanning failed");
+        let statements = Parser::new(tokens).parse().expect("parsing failed");
+        let mut printer = StmtPrinter(printer);
+        statements.iter().map(|stmt| printer.visit_stmt(stmt)).collect()
+    }
+
+    #[test]
+    fn prints_statements_as_s_expressions() {
+        assert_eq!(print_with(ASTPrinter, "var a = 1 + 2; { print a; a; } if (a) return; else while (true) print b;"), [
+            "(var a (+ 1 2))",
+            "(block (print a) (; a))",
+            "(if a (return) (while true (print b)))",
+        ]);
+        assert_eq!(print_with(ASTPrinter, "fun f(x, y) { return x; } class B < A { m() {} }"), [
+            "(fun f (x y) (return x))",
+            "(class B < A (method m ()))",
+        ]);
+    }
+
+    #[test]
+    fn prints_expressions_in_reverse_polish_notation() {
+        assert_eq!(print_with(RPNPrinter, "print (1 + 2) * 3; var a = f(1);"), [
+            "(print 1 2 + 3 *)",
+            "(var a 1 f call)",
+        ]);
+    }
+}
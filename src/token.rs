@@ -1,24 +1,147 @@
 use core::fmt;
 
 use super::TokenType;
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralValue {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    Nil,
+}
+
+impl fmt::Display for LiteralValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LiteralValue::Number(n) => write!(f, "{}", n),
+            LiteralValue::Str(s) => write!(f, "{}", s),
+            LiteralValue::Bool(b) => write!(f, "{}", b),
+            LiteralValue::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+/// What a piece of trivia is. Newlines are kept apart from other whitespace
+/// so tools can tell line structure without re-reading the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+
+/// Source text between tokens that doesn't affect the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
 
 #[derive(Debug, Clone)]
-pub struct Token<'a> {
+pub struct Token {
     pub kind: TokenType,
-    pub lexeme: &'a str,
-    pub literal: Option<&'a str>, 
+    pub lexeme: String,
+    pub literal: Option<LiteralValue>,
     pub line: usize,
+    /// 1-based column, in characters, of the first character of the lexeme.
+    pub column: usize,
+    pub span: Span,
+    /// Set by a scanner made `with_trivia`, and on ERROR tokens. Boxed so
+    /// that tokens without any of it, which is nearly all of them, stay small.
+    pub(crate) extra: Option<Box<TokenExtra>>,
 }
 
-impl<'a> Token<'a> {
-    pub fn new(kind: TokenType, lexeme: &'a str, literal: Option<&'a str>, line: usize) -> Self {
-        Token { kind , lexeme, literal, line }
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TokenExtra {
+    pub(crate) leading: Vec<Trivia>,
+    pub(crate) trailing: Vec<Trivia>,
+    /// The source text of an identifier whose lexeme was normalized.
+    pub(crate) source_text: Option<String>,
+    /// Why an ERROR token's text isn't a token.
+    pub(crate) error: Option<String>,
+}
+
+impl Token {
+    pub fn new(kind: TokenType, lexeme: &str, literal: Option<LiteralValue>, line: usize) -> Self {
+        Token {
+            kind,
+            lexeme: lexeme.to_string(),
+            literal,
+            line,
+            column: 1,
+            span: Span::default(),
+            extra: None,
+        }
+    }
+
+    pub fn with_position(mut self, span: Span, column: usize) -> Self {
+        self.span = span;
+        self.column = column;
+        self
+    }
+
+    /// Trivia before the token, from the end of the previous token's trailing
+    /// trivia on.
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        self.extra.as_ref().map_or(&[], |extra| &extra.leading)
+    }
+
+    /// Trivia after the token up to, but not including, the next newline.
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        self.extra.as_ref().map_or(&[], |extra| &extra.trailing)
+    }
+
+    /// The token's text as written in the source. This only differs from the
+    /// lexeme for an identifier that wasn't in NFC, which keeps its original
+    /// text whether or not the scanner kept trivia.
+    pub fn source_text(&self) -> &str {
+        self.extra.as_ref().and_then(|extra| extra.source_text.as_deref()).unwrap_or(&self.lexeme)
+    }
+
+    /// The token's text together with its trivia. Joining the full text of
+    /// every token from a scanner made `with_trivia` gives back the source.
+    pub fn full_text(&self) -> String {
+        let mut text = String::new();
+        for trivia in self.leading_trivia() {
+            text += &trivia.text;
+        }
+        text += self.source_text();
+        for trivia in self.trailing_trivia() {
+            text += &trivia.text;
+        }
+        text
+    }
+
+    /// For an ERROR token, the first error the scanner reported in its text.
+    pub fn error(&self) -> Option<&str> {
+        self.extra.as_ref().and_then(|extra| extra.error.as_deref())
+    }
+
+    /// The value of a NUMBER token, whichever way the literal was written.
+    pub fn number(&self) -> Option<f64> {
+        match self.literal {
+            Some(LiteralValue::Number(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a string fragment picking up again after an
+    /// interpolated expression, like the `}!"` of `"Hello ${name}!"`.
+    pub fn resumes_string(&self) -> bool {
+        matches!(self.kind, TokenType::STRING | TokenType::INTERPOLATION) && self.lexeme.starts_with('}')
     }
 }
 
-impl<'a> fmt::Display for Token<'a> {
+impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?} {} {}", self.kind, self.lexeme, self.literal.unwrap_or(" "))
+        match (&self.literal, self.error()) {
+            (Some(literal), _) => write!(f, "{:?} {} {}", self.kind, self.lexeme, literal),
+            (None, Some(error)) => write!(f, "{:?} {} {}", self.kind, self.lexeme, error),
+            (None, None) => write!(f, "{:?} {}  ", self.kind, self.lexeme),
+        }
     }
 }
 
@@ -1,4 +1,5 @@
-#[derive(Debug, Copy, Clone)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum TokenType {
     // Single-character tokens.
     LEFT_PAREN, RIGHT_PAREN, LEFT_BRACE, RIGHT_BRACE,
@@ -12,10 +13,17 @@ pub enum TokenType {
 
     // Literals.
     IDENTIFIER, STRING, NUMBER,
+    // A string fragment ending in `${`; the fragment after the embedded
+    // expression is another INTERPOLATION or, at the end, a STRING.
+    INTERPOLATION,
 
     // Keyworkds.
     AND, CLASS, ELSE, FALSE, FUN, FOR, IF, NIL, OR,
     PRINT, RETURN, SUPER, THIS, TRUE, VAR, WHILE,
 
+    // Source text the scanner reported an error in, like a stray `@` or an
+    // unterminated string. `Token::error` gives the error's message.
+    ERROR,
+
     EOF
 }
@@ -0,0 +1,176 @@
+use core::fmt;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{LiteralValue, RuntimeError};
+use crate::callable::LoxCallable;
+use crate::class::LoxClass;
+use crate::instance::LoxInstance;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Callable(Rc<dyn LoxCallable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+impl Value {
+    /// Lox follows Ruby's rule: `false` and `nil` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// The name of the value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::Callable(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            // Functions, classes and instances only compare equal to themselves.
+            (Value::Callable(l), Value::Callable(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Callable(c) => write!(f, "{}", c),
+            Value::Class(c) => write!(f, "{}", c),
+            Value::Instance(i) => write!(f, "{}", i.borrow()),
+        }
+    }
+}
+
+impl From<LiteralValue> for Value {
+    fn from(literal: LiteralValue) -> Self {
+        match literal {
+            LiteralValue::Number(n) => Value::Number(n),
+            LiteralValue::Str(s) => Value::Str(s),
+            LiteralValue::Bool(b) => Value::Bool(b),
+            LiteralValue::Nil => Value::Nil,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+fn expected(type_name: &str, value: &Value) -> RuntimeError {
+    RuntimeError::message(&format!("Expected a {} but got {}.", type_name, value.type_name()))
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = RuntimeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            other => Err(expected("boolean", other)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = RuntimeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(n) => Ok(*n),
+            other => Err(expected("number", other)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = RuntimeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Str(s) => Ok(s.clone()),
+            other => Err(expected("string", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converts_rust_values_into_lox_values() {
+        assert_eq!(Value::from(true), Value::Bool(true));
+        assert_eq!(Value::from(1.5), Value::Number(1.5));
+        assert_eq!(Value::from("a"), Value::Str(String::from("a")));
+        assert_eq!(Value::from(()), Value::Nil);
+        assert_eq!(Value::from(None::<f64>), Value::Nil);
+        assert_eq!(Value::from(Some(String::from("b"))), Value::Str(String::from("b")));
+    }
+
+    #[test]
+    fn converts_lox_values_into_rust_values() {
+        assert_eq!(f64::try_from(&Value::Number(2.0)).unwrap(), 2.0);
+        assert!(bool::try_from(&Value::Bool(false)).is_ok());
+        assert_eq!(String::try_from(&Value::Str(String::from("s"))).unwrap(), "s");
+        assert_eq!(f64::try_from(&Value::Nil).unwrap_err().to_string(), "Expected a number but got nil.");
+    }
+}
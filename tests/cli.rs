@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the `rlox` binary with `args`, feeding it `stdin`.
+fn rlox(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start rlox");
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Writes `source` to a script called `name` and returns its path.
+fn script(name: &str, source: &str) -> String {
+    let path: PathBuf = [env!("CARGO_TARGET_TMPDIR"), name].iter().collect();
+    std::fs::write(&path, source).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn a_clean_run_exits_with_zero() {
+    let output = rlox(&[&script("clean.lox", "print 1 + 2;")], "");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn bad_usage_exits_with_64() {
+    let output = rlox(&["--error-format=xml"], "");
+
+    assert_eq!(output.status.code(), Some(64));
+    assert!(stderr(&output).contains("Usage: rlox"));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn static_errors_exit_with_65() {
+    for (name, source) in [("parse.lox", "print 1 +;"), ("scan.lox", "print @;"), ("resolve.lox", "{ var a = 1; var a = 2; }")] {
+        let output = rlox(&[&script(name, source)], "");
+
+        assert_eq!(output.status.code(), Some(65), "{}", source);
+        assert_eq!(stdout(&output), "");
+    }
+}
+
+#[test]
+fn runtime_errors_exit_with_70() {
+    let output = rlox(&[&script("runtime.lox", "print 1;\nprint -\"a\";")], "");
+
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains("Operand must be a number."));
+}
+
+#[test]
+fn runaway_recursion_exits_with_70() {
+    let output = rlox(&[&script("recursion.lox", "fun f() { f(); } f();")], "");
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).contains("Stack overflow."));
+}
+
+#[test]
+fn an_unreadable_script_exits_with_74() {
+    let output = rlox(&["no/such/script.lox"], "");
+
+    assert_eq!(output.status.code(), Some(74));
+    assert!(stderr(&output).contains("Could not read 'no/such/script.lox'"));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn print_goes_to_stdout_and_diagnostics_to_stderr() {
+    let output = rlox(&[&script("streams.lox", "print \"out\";\nprint undefined;")], "");
+
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "out\n");
+    assert!(!stdout(&output).contains("Undefined variable"));
+    assert!(stderr(&output).contains("Undefined variable 'undefined'."));
+    assert!(!stderr(&output).contains("out"));
+}
+
+#[test]
+fn the_repl_keeps_going_after_a_bad_line() {
+    let output = rlox(&[], "var a = 1;\nprint a +;\nprint nope;\nprint a + 1;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    // One prompt per line read, plus the one answered by end of input.
+    assert_eq!(stdout(&output), "> > > > 2\n> ");
+    assert!(stderr(&output).contains("Expect expression."));
+    assert!(stderr(&output).contains("Undefined variable 'nope'."));
+}
+
+#[test]
+fn tokens_are_printed_even_when_scanning_fails() {
+    let output = rlox(&["tokens", "--json", "-e", "a @"], "");
+
+    assert_eq!(output.status.code(), Some(65));
+    let lines: Vec<String> = stdout(&output).lines().map(String::from).collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with(r#"{"kind":"ERROR","lexeme":"@","literal":null,"error":"Unexpected character.""#), "{}", lines[1]);
+    assert!(stderr(&output).contains("Unexpected character."));
+}
+
+#[test]
+fn check_reports_parse_errors_after_scan_errors() {
+    let output = rlox(&["check", "-"], "var a = 1 @;\nprint (;\n");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("Unexpected character."));
+    assert!(stderr(&output).contains("Expect expression."));
+}
+
+#[test]
+fn the_repl_writes_a_sarif_log_for_every_line() {
+    let output = rlox(&["--error-format=sarif", "repl"], "var a = 1;\nprint nope;\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output).matches("\"$schema\"").count(), 2);
+    assert_eq!(stderr(&output).matches("\"results\": []").count(), 1);
+    assert!(stderr(&output).contains("Undefined variable 'nope'."));
+}
@@ -0,0 +1,245 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use rlox::expr::{ASTPrinter, ExprVisitor};
+use rlox::stmt::Stmt;
+use rlox::{Error, Lox, Parser, Scanner, TokenType, Value};
+
+/// A `Write` the test keeps a handle on after giving it to the interpreter.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn eval_returns_the_value_of_the_last_expression() {
+    let mut lox = Lox::new();
+
+    assert_eq!(lox.eval("1 + 2").unwrap(), Value::Number(3.0));
+    assert_eq!(lox.eval("\"a\" + \"b\";").unwrap(), Value::Str(String::from("ab")));
+    assert_eq!(lox.eval("var a = 1;").unwrap(), Value::Nil);
+    assert_eq!(lox.eval("fun f(x) { return x * 2; } f(21)").unwrap(), Value::Number(42.0));
+}
+
+#[test]
+fn names_that_look_the_same_are_the_same() {
+    let mut lox = Lox::new();
+    lox.run("var caf\u{e9} = 1; var _count = 0; var 数 = 2;").unwrap();
+
+    assert_eq!(lox.eval("cafe\u{301} + _count + 数").unwrap(), Value::Number(3.0));
+    assert_eq!(lox.global("caf\u{e9}"), Some(Value::Number(1.0)));
+    assert_eq!(lox.global("cafe\u{301}"), Some(Value::Number(1.0)));
+}
+
+#[test]
+fn the_embedding_api_normalizes_names() {
+    let mut lox = Lox::new();
+    lox.run("fun caf\u{e9}() { return 1; } class R\u{e9}sum\u{e9} { init() { this.na\u{ef}ve = 2; } r\u{e9}p() { return 3; } }").unwrap();
+    let instance = lox.eval("R\u{e9}sum\u{e9}()").unwrap();
+    lox.register_fn("cafe\u{301}_au_lait", 0, |_| Ok(4.0));
+
+    assert_eq!(lox.call_function("cafe\u{301}", vec![]).unwrap(), Value::Number(1.0));
+    assert_eq!(lox.get_property(&instance, "nai\u{308}ve").unwrap(), Value::Number(2.0));
+    assert_eq!(lox.call_method(&instance, "re\u{301}p", vec![]).unwrap(), Value::Number(3.0));
+    assert_eq!(lox.eval("caf\u{e9}_au_lait()").unwrap(), Value::Number(4.0));
+}
+
+#[test]
+fn runaway_recursion_is_an_error_on_any_thread() {
+    // Test threads get a small stack, which the interpreter grows as it needs.
+    let mut lox = Lox::new();
+    lox.run("fun down(n) { if (n > 0) down(n - 1); }").unwrap();
+
+    assert!(lox.run(&format!("down({});", rlox::interpreter::MAX_CALL_DEPTH - 1)).is_ok());
+    match lox.run("fun f() { f(); } f();") {
+        Err(Error::Runtime(error)) => assert_eq!(error.message, "Stack overflow."),
+        other => panic!("expected a stack overflow, got {:?}", other.map_err(|error| error.to_string())),
+    }
+}
+
+#[test]
+fn deeply_nested_programs_run_on_any_thread() {
+    let mut lox = Lox::new();
+    let depth = 120;
+    let source = format!("{}var a = {}1{};{}", "{".repeat(depth), "(".repeat(depth), ")".repeat(depth), "}".repeat(depth));
+
+    assert!(lox.run(&source).is_ok());
+}
+
+#[test]
+fn globals_persist_across_calls() {
+    let mut lox = Lox::new();
+    lox.run("var count = 0; fun bump() { count = count + 1; return count; }").unwrap();
+    lox.run("bump(); bump();").unwrap();
+
+    assert_eq!(lox.eval("bump()").unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn print_goes_to_the_chosen_output() {
+    let out = SharedBuffer::default();
+    let mut lox = Lox::with_output(out.clone());
+    lox.run("print \"hello\"; print 1 + 1;").unwrap();
+
+    assert_eq!(out.contents(), "hello\n2\n");
+
+    let other = SharedBuffer::default();
+    lox.set_output(other.clone());
+    lox.run("print nil;").unwrap();
+    assert_eq!(out.contents(), "hello\n2\n");
+    assert_eq!(other.contents(), "nil\n");
+}
+
+#[test]
+fn errors_are_grouped_by_phase() {
+    let mut lox = Lox::new();
+
+    assert!(matches!(lox.eval("@ #"), Err(Error::Syntax { scan, parse }) if scan.len() == 2 && parse.is_empty()));
+    assert!(matches!(lox.eval("print ;"), Err(Error::Syntax { scan, parse }) if scan.is_empty() && parse.len() == 1));
+    assert!(matches!(lox.eval("return 1;"), Err(Error::Resolve(_))));
+
+    // The parser carries on past the scanner's errors and reports its own.
+    let error = lox.eval("var a = 1 @;\nprint (;").unwrap_err();
+    assert_eq!(error.to_string(), "[line 1] Error: Unexpected character.\n[line 2] Error at ';': Expect expression.");
+    assert_eq!(error.diagnostics().iter().map(|diagnostic| diagnostic.code).collect::<Vec<_>>(), ["E0001", "E0100"]);
+
+    let error = lox.eval("-\"a\"").unwrap_err();
+    assert!(matches!(error, Error::Runtime(_)));
+    assert_eq!(error.to_string(), "[line 1] Operand must be a number.");
+    assert_eq!(error.diagnostics()[0].code, "E0300");
+}
+
+#[test]
+fn a_runtime_error_leaves_earlier_definitions_in_place() {
+    let mut lox = Lox::new();
+    assert!(lox.run("var a = 1; a = -nil; var b = 2;").is_err());
+
+    assert_eq!(lox.eval("a").unwrap(), Value::Number(1.0));
+    assert!(lox.eval("b").is_err());
+}
+
+#[test]
+fn the_phases_are_usable_on_their_own() {
+    let mut scanner = Scanner::new("print 1 + 2;");
+    let tokens = scanner.scan_tokens().unwrap();
+    assert_eq!(tokens.iter().map(|token| token.kind).collect::<Vec<_>>(), [
+        TokenType::PRINT, TokenType::NUMBER, TokenType::PLUS, TokenType::NUMBER, TokenType::SEMICOLON, TokenType::EOF,
+    ]);
+
+    let statements = Parser::new(tokens).parse().unwrap();
+    match &statements[..] {
+        [Stmt::Print(expr)] => assert_eq!(ASTPrinter.visit(expr), "(+ 1 2)"),
+        other => panic!("unexpected statements {:?}", other),
+    }
+
+    assert!(rlox::compile("{ var a = a; }").is_err());
+}
+
+#[test]
+fn native_functions_are_callable_from_lox() {
+    let mut lox = Lox::new();
+    lox.register_fn("config", 1, |args| {
+        let key = String::try_from(&args[0])?;
+        Ok(match key.as_str() {
+            "retries" => Some(3.0),
+            _ => None,
+        })
+    });
+    lox.register_fn("shout", 1, |args| Ok(String::try_from(&args[0])?.to_uppercase()));
+
+    assert_eq!(lox.eval("config(\"retries\") + 1").unwrap(), Value::Number(4.0));
+    assert_eq!(lox.eval("config(\"missing\")").unwrap(), Value::Nil);
+    assert_eq!(lox.eval("shout(\"hi\")").unwrap(), Value::Str(String::from("HI")));
+}
+
+#[test]
+fn native_functions_check_arguments_and_report_errors_at_the_call() {
+    let mut lox = Lox::new();
+    lox.register_fn("half", 1, |args| Ok(f64::try_from(&args[0])? / 2.0));
+
+    assert_eq!(lox.eval("half(1, 2)").unwrap_err().to_string(), "[line 1] Expected 1 arguments but got 2.");
+    assert_eq!(lox.eval("\nhalf(\"x\")").unwrap_err().to_string(), "[line 2] Expected a number but got string.");
+}
+
+#[test]
+fn lox_functions_are_callable_from_rust() {
+    let mut lox = Lox::new();
+    lox.run("
+        var calls = 0;
+        fun fib(n) {
+            calls = calls + 1;
+            if (n < 2) return n;
+            return fib(n - 1) + fib(n - 2);
+        }
+    ").unwrap();
+
+    let expected = [0.0, 1.0, 1.0, 2.0, 3.0, 5.0, 8.0, 13.0];
+    for (n, fib) in expected.into_iter().enumerate() {
+        assert_eq!(lox.call_function("fib", vec![Value::from(n as f64)]).unwrap(), Value::Number(fib));
+    }
+    assert_eq!(lox.global("calls"), Some(Value::Number(100.0)));
+
+    let fib = lox.global("fib").unwrap();
+    assert_eq!(lox.call(&fib, vec![10.0.into()]).unwrap(), Value::Number(55.0));
+}
+
+#[test]
+fn methods_and_fields_are_reachable_from_rust() {
+    let mut lox = Lox::new();
+    lox.run("
+        class Counter {
+            init(step) { this.count = 0; this.step = step; }
+            bump() { this.count = this.count + this.step; return this; }
+        }
+        var counter = Counter(2);
+    ").unwrap();
+
+    let counter = lox.global("counter").unwrap();
+    for _ in 0..3 {
+        assert_eq!(lox.call_method(&counter, "bump", vec![]).unwrap(), counter);
+    }
+    assert_eq!(lox.get_property(&counter, "count").unwrap(), Value::Number(6.0));
+
+    let Value::Instance(instance) = &counter else { panic!("expected an instance, got {}", counter) };
+    let mut names: Vec<String> = instance.borrow().field_names().map(String::from).collect();
+    names.sort();
+    assert_eq!(names, ["count", "step"]);
+    assert_eq!(instance.borrow().field("step"), Some(&Value::Number(2.0)));
+
+    let class = lox.global("Counter").unwrap();
+    let other = lox.call(&class, vec![5.0.into()]).unwrap();
+    lox.call_method(&other, "bump", vec![]).unwrap();
+    assert_eq!(lox.get_property(&other, "count").unwrap(), Value::Number(5.0));
+    assert_eq!(lox.get_property(&counter, "count").unwrap(), Value::Number(6.0));
+}
+
+#[test]
+fn calls_from_rust_report_runtime_errors() {
+    let mut lox = Lox::new();
+    lox.run("fun fail(x) {\n  return -x;\n}\nvar n = 1;").unwrap();
+
+    let message = |result: Result<Value, Error>| result.unwrap_err().to_string();
+    assert_eq!(message(lox.call_function("fail", vec![])), "Expected 1 arguments but got 0.");
+    assert_eq!(message(lox.call_function("fail", vec!["a".into()])), "[line 2] Operand must be a number.");
+    assert_eq!(message(lox.call_function("n", vec![])), "Can only call functions and classes.");
+    assert_eq!(message(lox.call_function("missing", vec![])), "Undefined variable 'missing'.");
+    assert_eq!(message(lox.get_property(&Value::Nil, "x")), "Only instances have properties.");
+
+    // A failed call leaves the session usable.
+    assert_eq!(lox.call_function("fail", vec![3.0.into()]).unwrap(), Value::Number(-3.0));
+}
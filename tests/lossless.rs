@@ -0,0 +1,115 @@
+use rlox::{Scanner, Token};
+
+/// A small xorshift generator, so the property test is repeatable without
+/// pulling in a crate.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn coin(&mut self) -> bool {
+        self.next() & 1 == 0
+    }
+
+    fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+        choices[self.next() as usize % choices.len()]
+    }
+}
+
+const TOKENS: &[&str] = &[
+    "var", "fun", "class", "print", "return", "this", "super", "nil", "true", "false",
+    "a", "_b2", "my_var", "héllo", "he\u{301}llo", "変数", "ünïcode", "0", "42", "3.25", "0xFF", "0b1010", "0o755", "1_000", "6.02e23", "1e-9",
+    "\"\"", "\"text\"", "\"é\\n\\t\\u{1F600}\"", "\"multi\nline\"", "\"${a}\"", "\"x ${ 1 + \"${b}\" } y\"",
+    "(", ")", "{", "}", ",", ".", "-", "+", ";", "/", "*", "!", "!=", "=", "==", "<", "<=", ">", ">=",
+];
+
+const TRIVIA: &[&str] = &[
+    " ", "  ", "\t", "\n", "\r\n", "\n\n", "// comment", "// ünïcode ✓", "/* block */", "/* a\n b */",
+    "/* /* nested */ */", "/**/",
+];
+
+/// Tokens with trivia between them. Tokens that would run together are
+/// always separated, so the source scans to the tokens it was made from.
+fn random_source(rng: &mut Rng) -> String {
+    let mut source = String::new();
+    for _ in 0..rng.next() % 40 {
+        if rng.coin() {
+            source += rng.pick(TRIVIA);
+            // A line comment runs to the end of the line.
+            if source.ends_with(|c| c != '\n') && source.lines().last().is_some_and(|line| line.contains("//")) {
+                source.push('\n');
+            }
+        }
+        source += rng.pick(TOKENS);
+        source.push(' ');
+    }
+    if rng.coin() {
+        source += rng.pick(TRIVIA);
+    }
+    source
+}
+
+#[test]
+fn tokens_with_trivia_reproduce_the_source() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..2000 {
+        let source = random_source(&mut rng);
+        let tokens = Scanner::new(&source).with_trivia().scan_tokens()
+            .unwrap_or_else(|errors| panic!("{:?} failed to scan: {:?}", source, errors));
+
+        assert_eq!(tokens.iter().map(Token::full_text).collect::<String>(), source);
+
+        // Every piece sits exactly where its span says.
+        for token in &tokens {
+            assert_eq!(&source[token.span.start_byte..token.span.end_byte], token.source_text());
+            for trivia in token.leading_trivia().iter().chain(token.trailing_trivia()) {
+                assert_eq!(&source[trivia.span.start_byte..trivia.span.end_byte], trivia.text);
+            }
+        }
+    }
+}
+
+/// Anything at all, weighted towards the characters that start or end
+/// strings, comments and interpolations.
+fn random_garbage(rng: &mut Rng) -> String {
+    const PIECES: &[&str] = &[
+        "\"", "/*", "*/", "//", "/", "*", "${", "{", "}", "\\", "\\u{", "\\q", "\n", " ", "@", "#", "€", "\u{301}",
+        "0x", "0b2", "1__", "1e", "a", "_", "é", "1", ".", "$",
+    ];
+
+    (0..rng.next() % 30).map(|_| rng.pick(PIECES)).collect()
+}
+
+#[test]
+fn broken_sources_still_scan_to_the_end_and_round_trip() {
+    let mut rng = Rng(0xdead_beef_cafe_f00d);
+
+    for _ in 0..5000 {
+        let source = random_garbage(&mut rng);
+        let tokens: Vec<Token> = Scanner::new(&source).with_trivia().filter_map(Result::ok).collect();
+
+        assert_eq!(tokens.iter().map(Token::full_text).collect::<String>(), source);
+        assert_eq!(tokens.iter().filter(|token| token.kind == rlox::TokenType::EOF).count(), 1);
+        assert_eq!(tokens.last().map(|token| token.kind), Some(rlox::TokenType::EOF));
+    }
+}
+
+#[test]
+fn trivia_does_not_change_the_tokens() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..500 {
+        let source = random_source(&mut rng);
+        let plain = Scanner::new(&source).scan_tokens().unwrap();
+        let with_trivia = Scanner::new(&source).with_trivia().scan_tokens().unwrap();
+
+        let kinds = |tokens: &[Token]| tokens.iter().map(|token| (token.kind, token.span)).collect::<Vec<_>>();
+        assert_eq!(kinds(&plain), kinds(&with_trivia));
+    }
+}